@@ -1,16 +1,25 @@
-use ft_api::{
-    config_env_var, locations::FtApiCampusLocationsRequest, AuthInfo, FtApiToken, FtCampusId,
-    FtClient, FtClientReqwestConnector, FtFilterField, FtFilterOption, GS_CAMPUS_ID,
-};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use slack_morphism::prelude::*;
-use std::sync::Arc;
+use std::iter::Peekable;
+use std::str::SplitWhitespace;
+
+use crate::location::{LocationError, LocationResolver};
 
-use crate::WAKEUP_WORD;
+use crate::error::{Action, BotError};
+use crate::grammar::{ArgSpec, CommandNode, ParseError};
+use crate::i18n::Locale;
+use crate::user::UserStore;
 
 #[derive(Debug)]
-pub struct BotTask {
-    pub message_context: SlackMessageContext,
+pub enum BotTask {
+    // Straight from Slack. The worker looks up the sender before parsing.
+    Request(SlackRequest),
+    // A destructive command its requester confirmed, run as is.
+    Confirmed {
+        message_context: SlackMessageContext,
+        command: GsctlCommand,
+    },
 }
 
 #[derive(Debug)]
@@ -28,7 +37,7 @@ impl From<String> for Error {
 pub enum SubCommand {
     Reset(ft_api::FtLoginId),
     Close(ft_api::FtLoginId, String),
-    Help,
+    ResetAt(ft_api::FtLoginId, ft_api::FtHost),
 }
 
 #[derive(Debug)]
@@ -37,13 +46,32 @@ pub enum GsctlCommand {
     Home(Option<SubCommand>),
     Goinfre(Option<SubCommand>),
     Update,
+    Link,
 }
 
 #[derive(Debug)]
 pub enum GsctlError {
-    Help,
+    Help(Vec<&'static str>),
     NotACommand,
-    Error(String),
+    Error(BotError),
+}
+
+impl From<BotError> for GsctlError {
+    fn from(err: BotError) -> Self {
+        GsctlError::Error(err)
+    }
+}
+
+impl From<ParseError> for GsctlError {
+    fn from(err: ParseError) -> Self {
+        GsctlError::Error(err.into())
+    }
+}
+
+impl From<LocationError> for GsctlError {
+    fn from(err: LocationError) -> Self {
+        GsctlError::Error(err.into())
+    }
 }
 
 fn check_hostname(raw_text: &str) -> bool {
@@ -52,120 +80,178 @@ fn check_hostname(raw_text: &str) -> bool {
     re.is_match(raw_text)
 }
 
+pub const GSCTL: CommandNode = CommandNode {
+    name: "gsctl",
+    help: "help.gsctl",
+    args: &[],
+    flags: &[],
+    subcommands: &[
+        CommandNode {
+            name: "reboot",
+            help: "help.reboot",
+            args: &[ArgSpec {
+                name: "host",
+                help: "help.reboot.host",
+                required: false,
+                validate: Some(check_hostname),
+            }],
+            flags: &[],
+            subcommands: &[],
+        },
+        CommandNode {
+            name: "home",
+            help: "help.home",
+            args: &[],
+            flags: &[],
+            subcommands: &[
+                CommandNode {
+                    name: "reset",
+                    help: "help.home.reset",
+                    args: &[],
+                    flags: &[],
+                    subcommands: &[],
+                },
+                CommandNode {
+                    name: "close",
+                    help: "help.home.close",
+                    args: &[],
+                    flags: &[],
+                    subcommands: &[],
+                },
+            ],
+        },
+        CommandNode {
+            name: "goinfre",
+            help: "help.goinfre",
+            args: &[],
+            flags: &[],
+            subcommands: &[CommandNode {
+                name: "reset",
+                help: "help.goinfre.reset",
+                args: &[],
+                flags: &[],
+                subcommands: &[],
+            }],
+        },
+        CommandNode {
+            name: "update",
+            help: "help.update",
+            args: &[],
+            flags: &[],
+            subcommands: &[],
+        },
+        CommandNode {
+            name: "link",
+            help: "help.link",
+            args: &[],
+            flags: &[],
+            subcommands: &[],
+        },
+    ],
+};
+
+pub fn help_text(locale: Locale, wakeup_word_for_user: &str, path: &[&str]) -> String {
+    match GSCTL.resolve(path) {
+        Some(node) => node.render_help(locale, wakeup_word_for_user, path),
+        None => GSCTL.render_help(locale, wakeup_word_for_user, &[]),
+    }
+}
+
 impl GsctlCommand {
+    // Commands that throw away a student's data and have to be confirmed.
+    pub fn destructive_action(&self) -> Option<Action> {
+        match self {
+            GsctlCommand::Home(Some(SubCommand::Reset(_))) => Some(Action::HomeReset),
+            GsctlCommand::Goinfre(Some(SubCommand::ResetAt(..))) => Some(Action::GoinfreReset),
+            _ => None,
+        }
+    }
+
+    pub fn action(&self) -> Option<Action> {
+        match self {
+            GsctlCommand::Reboot(_) => Some(Action::Reboot),
+            GsctlCommand::Home(Some(SubCommand::Reset(_))) => Some(Action::HomeReset),
+            GsctlCommand::Home(Some(SubCommand::Close(..))) => Some(Action::HomeClose),
+            GsctlCommand::Goinfre(Some(SubCommand::ResetAt(..))) => Some(Action::GoinfreReset),
+            GsctlCommand::Update => Some(Action::Update),
+            GsctlCommand::Link => Some(Action::Link),
+            _ => None,
+        }
+    }
+
+    pub fn login(&self) -> Option<&ft_api::FtLoginId> {
+        match self {
+            GsctlCommand::Home(Some(SubCommand::Reset(login)))
+            | GsctlCommand::Home(Some(SubCommand::Close(login, _)))
+            | GsctlCommand::Goinfre(Some(SubCommand::ResetAt(login, _))) => Some(login),
+            _ => None,
+        }
+    }
+
+    // What the command touches on the remote side. Commands sharing a key
+    // are run one after another.
+    pub fn job_keys(&self) -> Vec<String> {
+        match self {
+            GsctlCommand::Reboot(host) => vec![format!("host:{host}")],
+            GsctlCommand::Home(Some(SubCommand::Reset(login)))
+            | GsctlCommand::Home(Some(SubCommand::Close(login, _))) => {
+                vec![format!("login:{login}")]
+            }
+            GsctlCommand::Goinfre(Some(SubCommand::ResetAt(login, host))) => {
+                vec![format!("login:{login}"), format!("host:{host}")]
+            }
+            _ => vec![],
+        }
+    }
+
     pub async fn from(
         context: &SlackMessageContext,
-        ft_client: Arc<FtClient<FtClientReqwestConnector>>,
+        wakeup_word: &str,
+        locations: &LocationResolver,
+        users: &UserStore,
     ) -> Result<Self, GsctlError> {
-        let mut token = context.text.split_whitespace();
-
-        if let Some(WAKEUP_WORD) = token.next() {
-            match token.next() {
-                Some(subcommand) => {
-                    match subcommand {
-                        "reboot" => {
-                            let location = match token.next() {
-                                Some(location) if check_hostname(location) && context.is_admin => {
-                                    ft_api::FtHost(location.to_string())
-                                }
-                                Some(_) => return Err(GsctlError::Help),
-                                None => {
-                                    let info = AuthInfo::build_from_env().unwrap();
-                                    let token = FtApiToken::try_get(info).await.unwrap();
-                                    let session = ft_client.open_session(&token);
-
-                                    let res = session
-                                        .campus_id_locations(
-                                            FtApiCampusLocationsRequest::new(FtCampusId::new(
-                                                GS_CAMPUS_ID,
-                                            ))
-                                            .with_filter(vec![FtFilterOption::new(
-                                                FtFilterField::Active,
-                                                vec!["true".to_string()],
-                                            )]),
-                                        )
-                                        .await
-                                        .unwrap();
-
-                                    match res.location.into_iter().find(|lo| {
-                                        if let Some(name) = &lo.user.login {
-                                            name.to_string() == context.real_name
-                                        } else {
-                                            false
-                                        }
-                                    }) {
-                                        Some(location) => location.host,
-                                        None => {
-                                            return Err(GsctlError::Error(
-                                                "Location not found!".to_string(),
-                                            ))
-                                        }
-                                    }
-                                }
-                            };
-                            Ok(GsctlCommand::Reboot(location))
-                        }
-                        "home" => {
-                            let subcommand = match token.next() {
-                                Some("reset") => Some(SubCommand::Reset(ft_api::FtLoginId(
-                                    context.real_name.clone(),
-                                ))),
-                                Some("close") => {
-                                    let info = AuthInfo::build_from_env().unwrap();
-                                    let token = FtApiToken::try_get(info).await.unwrap();
-                                    let session = ft_client.open_session(&token);
-
-                                    let res = session
-                                        .campus_id_locations(
-                                            FtApiCampusLocationsRequest::new(FtCampusId::new(
-                                                GS_CAMPUS_ID,
-                                            ))
-                                            .with_filter(vec![FtFilterOption::new(
-                                                FtFilterField::Active,
-                                                vec!["true".to_string()],
-                                            )]),
-                                        )
-                                        .await
-                                        .unwrap();
-
-                                    let location = match res.location.into_iter().find(|lo| {
-                                        if let Some(name) = &lo.user.login {
-                                            name.to_string() == context.real_name
-                                        } else {
-                                            false
-                                        }
-                                    }) {
-                                        Some(location) => format!("iqn.fr.42:{}", location.host),
-                                        None => {
-                                            return Err(GsctlError::Error(
-                                                "location not found!".to_string(),
-                                            ))
-                                        }
-                                    };
-
-                                    Some(SubCommand::Close(
-                                        ft_api::FtLoginId(context.real_name.clone()),
-                                        location,
-                                    ))
-                                }
-                                _ => Some(SubCommand::Help),
-                            };
-                            Ok(GsctlCommand::Home(subcommand))
-                        }
-                        "goinfre" => {
-                            let subcommand = match token.next() {
-                                Some("reset") => Some(SubCommand::Reset(ft_api::FtLoginId(
-                                    context.real_name.clone(),
-                                ))),
-                                _ => Some(SubCommand::Help),
-                            };
-                            Ok(GsctlCommand::Goinfre(subcommand))
-                        }
-                        "update" => Ok(GsctlCommand::Update),
-                        _ => Err(GsctlError::Help),
-                    }
+        if let Some(token) = context.command_tokens(wakeup_word) {
+            let parsed = GSCTL.parse_tokens(token)?;
+            if parsed.help {
+                return Err(GsctlError::Help(parsed.path));
+            }
+            // Commands acting on a student's own login or PC only run for
+            // accounts linked through 42 OAuth or by staff, never for a login
+            // guessed from a profile anyone can edit.
+            let linked_login = || match users.user(&context.user) {
+                Some(user) if user.source.is_trusted() => Ok(user.login),
+                Some(_) => Err(BotError::NotLinked),
+                None => Err(BotError::NotRegistered),
+            };
+
+            match parsed.path.as_slice() {
+                ["reboot"] => {
+                    let location = match parsed.arg("host") {
+                        Some(host) if context.is_admin => ft_api::FtHost(host.to_string()),
+                        Some(_) => return Err(BotError::StaffOnly(Action::Reboot).into()),
+                        None => locations.resolve(&linked_login()?).await?,
+                    };
+                    Ok(GsctlCommand::Reboot(location))
+                }
+                ["home", "reset"] => {
+                    Ok(GsctlCommand::Home(Some(SubCommand::Reset(linked_login()?))))
                 }
-                None => Err(GsctlError::Help),
+                ["home", "close"] => {
+                    let login = linked_login()?;
+                    let location = format!("iqn.fr.42:{}", locations.resolve(&login).await?);
+                    Ok(GsctlCommand::Home(Some(SubCommand::Close(login, location))))
+                }
+                ["goinfre", "reset"] => {
+                    let login = linked_login()?;
+                    let host = locations.resolve(&login).await?;
+                    Ok(GsctlCommand::Goinfre(Some(SubCommand::ResetAt(
+                        login, host,
+                    ))))
+                }
+                ["link"] => Ok(GsctlCommand::Link),
+                ["update"] if context.is_admin => Ok(GsctlCommand::Update),
+                ["update"] => Err(BotError::StaffOnly(Action::Update).into()),
+                path => Err(BotError::NotImplemented(path.join(" ")).into()),
             }
         } else {
             Err(GsctlError::NotACommand)
@@ -176,6 +262,14 @@ impl GsctlCommand {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::campus::CampusApi;
+    use crate::i18n::tr;
+    use crate::test_util::store_path;
+    use crate::user::LinkSource;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    const WAKEUP_WORD: &str = "<@UBOT>";
 
     #[test]
     fn hostname_regex_test_only_c1() {
@@ -204,14 +298,249 @@ mod tests {
 
         assert!(check_hostname(raw_text));
     }
+
+    #[test]
+    fn gsctl_tree_accepts_every_verb() {
+        for text in [
+            "reboot",
+            "reboot c1r1s1",
+            "home reset",
+            "home close",
+            "goinfre reset",
+            "update",
+            "link",
+        ] {
+            assert!(GSCTL.parse(text).is_ok(), "{text}");
+        }
+    }
+
+    #[test]
+    fn help_text_is_scoped_to_command() {
+        let help = help_text(Locale::En, "@gsctl", &["home"]);
+
+        assert!(help.contains("reset"));
+        assert!(help.contains("close"));
+        assert!(!help.contains("reboot"));
+    }
+
+    #[test]
+    fn help_keys_are_translated() {
+        fn walk(node: &CommandNode, keys: &mut Vec<&'static str>) {
+            keys.push(node.help);
+            keys.extend(node.args.iter().map(|arg| arg.help));
+            keys.extend(node.flags.iter().map(|flag| flag.help));
+            node.subcommands.iter().for_each(|child| walk(child, keys));
+        }
+
+        let mut keys = vec![];
+        walk(&GSCTL, &mut keys);
+
+        for locale in [Locale::Ko, Locale::En] {
+            for key in &keys {
+                assert_ne!(&tr(locale, key, &[]), key);
+            }
+        }
+    }
+
+    fn context(user: &str, text: &str) -> SlackMessageContext {
+        SlackMessageContext {
+            channel: SlackChannelId("C1".to_string()),
+            source: MessageSource::Mention {
+                ts: SlackTs("1".to_string()),
+                thread_ts: None,
+            },
+            user: SlackUserId(user.to_string()),
+            real_name: "someone-else".to_string(),
+            is_admin: false,
+            locale: Locale::En,
+            text: text.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn login_comes_from_user_store() {
+        let path = store_path("bot-cmd");
+        let users = UserStore::open(&path).unwrap();
+        users
+            .bind(
+                SlackUserId("U1".to_string()),
+                ft_api::FtLoginId("student".to_string()),
+                LinkSource::Verified,
+            )
+            .unwrap();
+        users
+            .bind(
+                SlackUserId("U3".to_string()),
+                ft_api::FtLoginId("guessed".to_string()),
+                LinkSource::Synced,
+            )
+            .unwrap();
+        let locations = LocationResolver::new(Arc::new(CampusApi::new(
+            Duration::from_secs(60),
+            Duration::from_secs(30),
+        )));
+        let text = format!("{WAKEUP_WORD} home reset");
+        let registered =
+            GsctlCommand::from(&context("U1", &text), WAKEUP_WORD, &locations, &users).await;
+        let unregistered =
+            GsctlCommand::from(&context("U2", &text), WAKEUP_WORD, &locations, &users).await;
+        let unlinked =
+            GsctlCommand::from(&context("U3", &text), WAKEUP_WORD, &locations, &users).await;
+
+        assert!(matches!(
+            registered,
+            Ok(GsctlCommand::Home(Some(SubCommand::Reset(login)))) if login.0 == "student"
+        ));
+        assert!(matches!(
+            unregistered,
+            Err(GsctlError::Error(BotError::NotRegistered))
+        ));
+        assert!(matches!(
+            unlinked,
+            Err(GsctlError::Error(BotError::NotLinked))
+        ));
+        let reboot = format!("{WAKEUP_WORD} reboot");
+        assert!(matches!(
+            GsctlCommand::from(&context("U3", &reboot), WAKEUP_WORD, &locations, &users).await,
+            Err(GsctlError::Error(BotError::NotLinked))
+        ));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn only_mentions_need_the_wakeup_word() {
+        let mention = context("U1", "home reset");
+        let slash = SlackMessageContext {
+            source: MessageSource::SlashCommand {
+                response_url: SlackResponseUrl(
+                    "https://hooks.slack.com/commands/1".parse().unwrap(),
+                ),
+            },
+            ..context("U1", "home reset")
+        };
+
+        let direct = SlackMessageContext {
+            source: MessageSource::DirectMessage {
+                ts: SlackTs("1".to_string()),
+                thread_ts: None,
+            },
+            ..context("U1", &format!("{WAKEUP_WORD} home reset"))
+        };
+
+        assert!(mention.command_tokens(WAKEUP_WORD).is_none());
+        assert_eq!(
+            slash
+                .command_tokens(WAKEUP_WORD)
+                .unwrap()
+                .collect::<Vec<_>>(),
+            vec!["home", "reset"]
+        );
+        assert_eq!(
+            direct
+                .command_tokens(WAKEUP_WORD)
+                .unwrap()
+                .collect::<Vec<_>>(),
+            vec!["home", "reset"]
+        );
+    }
+
+    #[test]
+    fn gsctl_tree_rejects_bad_hostname() {
+        assert_eq!(
+            GSCTL.parse("reboot asdf"),
+            Err(ParseError::InvalidArgument {
+                path: vec!["reboot"],
+                arg: "host",
+                token: "asdf".to_string(),
+            })
+        );
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MessageSource {
+    Mention {
+        ts: SlackTs,
+        thread_ts: Option<SlackTs>,
+    },
+    DirectMessage {
+        ts: SlackTs,
+        thread_ts: Option<SlackTs>,
+    },
+    SlashCommand {
+        response_url: SlackResponseUrl,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SlackRequest {
+    pub user: SlackUserId,
+    pub channel: SlackChannelId,
+    pub source: MessageSource,
+    pub text: String,
+}
+
+impl SlackRequest {
+    // The command as typed, without the mention. `None` when the message
+    // was not meant for the bot.
+    pub fn command_text(&self, wakeup_word: &str) -> Option<String> {
+        command_tokens(&self.text, &self.source, wakeup_word)
+            .map(|tokens| tokens.collect::<Vec<_>>().join(" "))
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct SlackMessageContext {
     pub channel: SlackChannelId,
-    pub ts: SlackTs,
-    pub thread_ts: Option<SlackTs>,
+    pub source: MessageSource,
+    pub user: SlackUserId,
     pub real_name: String,
     pub is_admin: bool,
+    pub locale: Locale,
     pub text: String,
 }
+
+impl SlackMessageContext {
+    // A context for answering a request without knowing who sent it. The
+    // sender is never treated as staff.
+    pub fn unknown_sender(request: SlackRequest, locale: Locale) -> Self {
+        SlackMessageContext {
+            channel: request.channel,
+            source: request.source,
+            user: request.user,
+            real_name: String::new(),
+            is_admin: false,
+            locale,
+            text: request.text,
+        }
+    }
+
+    pub fn request(&self) -> SlackRequest {
+        SlackRequest {
+            user: self.user.clone(),
+            channel: self.channel.clone(),
+            source: self.source.clone(),
+            text: self.text.clone(),
+        }
+    }
+
+    pub fn command_tokens(&self, wakeup_word: &str) -> Option<Peekable<SplitWhitespace<'_>>> {
+        command_tokens(&self.text, &self.source, wakeup_word)
+    }
+}
+
+// Mentions have to start with the bot's id. DMs and slash commands are
+// addressed to the bot already, a leading mention there is skipped.
+fn command_tokens<'a>(
+    text: &'a str,
+    source: &MessageSource,
+    wakeup_word: &str,
+) -> Option<Peekable<SplitWhitespace<'a>>> {
+    let mut tokens = text.split_whitespace().peekable();
+    let mentioned = tokens.next_if_eq(&wakeup_word).is_some();
+    match source {
+        MessageSource::Mention { .. } if !mentioned => None,
+        _ => Some(tokens),
+    }
+}
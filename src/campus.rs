@@ -0,0 +1,199 @@
+use ft_api::{
+    locations::FtApiCampusLocationsRequest, AuthInfo, FtApiToken, FtCampusId, FtClient,
+    FtClientReqwestConnector, FtFilterField, FtFilterOption, FtLocation, GS_CAMPUS_ID,
+};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(5 * 60);
+const LOCATIONS_PER_PAGE: u16 = 100;
+const MAX_LOCATION_PAGES: u16 = 20;
+
+// Holds one value for `ttl`. Callers that arrive while a fetch is running
+// wait for it instead of starting their own.
+#[derive(Debug)]
+pub struct Cached<T> {
+    ttl: Duration,
+    slot: Mutex<Option<(Instant, Arc<T>)>>,
+}
+
+impl<T> Cached<T> {
+    pub fn new(ttl: Duration) -> Self {
+        Cached {
+            ttl,
+            slot: Mutex::new(None),
+        }
+    }
+
+    pub async fn get_or_fetch<F, Fut, E>(&self, fetch: F) -> Result<Arc<T>, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let mut slot = self.slot.lock().await;
+        if let Some((fetched_at, value)) = slot.as_ref() {
+            if fetched_at.elapsed() < self.ttl {
+                return Ok(value.clone());
+            }
+        }
+
+        let value = Arc::new(fetch().await?);
+        *slot = Some((Instant::now(), value.clone()));
+        Ok(value)
+    }
+
+    pub async fn invalidate(&self) {
+        self.slot.lock().await.take();
+    }
+}
+
+pub struct CampusApi {
+    client: FtClient<FtClientReqwestConnector>,
+    token: Cached<FtApiToken>,
+    locations: Cached<Vec<FtLocation>>,
+}
+
+impl CampusApi {
+    pub fn new(token_lifetime: Duration, location_ttl: Duration) -> Self {
+        CampusApi {
+            client: FtClient::new(FtClientReqwestConnector::with_connector(
+                reqwest::Client::new(),
+            )),
+            token: Cached::new(token_lifetime.saturating_sub(TOKEN_REFRESH_MARGIN)),
+            locations: Cached::new(location_ttl),
+        }
+    }
+
+    pub async fn token(&self) -> Result<Arc<FtApiToken>, crate::Error> {
+        self.token
+            .get_or_fetch(|| async {
+                debug!("Fetching a new 42 API token");
+                let info = AuthInfo::build_from_env()?;
+                FtApiToken::try_get(info).await
+            })
+            .await
+    }
+
+    // Every active location on campus, following pages until a short one.
+    pub async fn active_locations(&self) -> Result<Arc<Vec<FtLocation>>, crate::Error> {
+        self.locations
+            .get_or_fetch(|| async {
+                let token = self.token().await?;
+                let session = self.client.open_session(&token);
+
+                fetch_pages(LOCATIONS_PER_PAGE, MAX_LOCATION_PAGES, |page| {
+                    let session = &session;
+                    async move {
+                        let res = session
+                            .campus_id_locations(
+                                FtApiCampusLocationsRequest::new(FtCampusId::new(GS_CAMPUS_ID))
+                                    .with_filter(vec![FtFilterOption::new(
+                                        FtFilterField::Active,
+                                        vec!["true".to_string()],
+                                    )])
+                                    .with_page(page)
+                                    .with_per_page(LOCATIONS_PER_PAGE),
+                            )
+                            .await;
+
+                        match res {
+                            Ok(res) => Ok(res.location),
+                            Err(err) => {
+                                // A revoked token would fail every request
+                                // until it expires, so fetch a fresh one next
+                                // time.
+                                self.token.invalidate().await;
+                                Err(err)
+                            }
+                        }
+                    }
+                })
+                .await
+            })
+            .await
+    }
+}
+
+// Collects pages until a short one. Running out of pages is an error: a cut
+// off list would make everyone on the missing pages look logged out.
+async fn fetch_pages<T, F, Fut>(
+    per_page: u16,
+    max_pages: u16,
+    mut fetch_page: F,
+) -> Result<Vec<T>, crate::Error>
+where
+    F: FnMut(u16) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, crate::Error>>,
+{
+    let mut items = vec![];
+    for page in 1..=max_pages {
+        let batch = fetch_page(page).await?;
+        let last = batch.len() < usize::from(per_page);
+        items.extend(batch);
+        if last {
+            return Ok(items);
+        }
+    }
+
+    Err(format!("more than {max_pages} pages of {per_page}, the list would be incomplete").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn cached_value_is_reused_until_ttl() {
+        let cache = Cached::new(Duration::from_millis(50));
+        let fetches = AtomicUsize::new(0);
+        let fetch = || async { Ok::<_, ()>(fetches.fetch_add(1, Ordering::SeqCst)) };
+
+        assert_eq!(*cache.get_or_fetch(fetch).await.unwrap(), 0);
+        assert_eq!(*cache.get_or_fetch(fetch).await.unwrap(), 0);
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(*cache.get_or_fetch(fetch).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn pages_are_followed_until_a_short_one() {
+        let pages = |sizes: Vec<usize>| {
+            move |page: u16| {
+                let size = sizes[usize::from(page) - 1];
+                async move { Ok(vec![page; size]) }
+            }
+        };
+
+        let items = fetch_pages(2, 3, pages(vec![2, 2, 1])).await.unwrap();
+        assert_eq!(items, vec![1, 1, 2, 2, 3]);
+        assert!(fetch_pages(2, 3, pages(vec![2, 2, 2])).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn failed_fetch_is_not_cached() {
+        let cache = Cached::new(Duration::from_secs(60));
+
+        assert!(cache
+            .get_or_fetch(|| async { Err::<u8, _>("down") })
+            .await
+            .is_err());
+        assert_eq!(
+            *cache
+                .get_or_fetch(|| async { Ok::<_, ()>(7) })
+                .await
+                .unwrap(),
+            7
+        );
+        cache.invalidate().await;
+        assert_eq!(
+            *cache
+                .get_or_fetch(|| async { Ok::<_, ()>(8) })
+                .await
+                .unwrap(),
+            8
+        );
+    }
+}
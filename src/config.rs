@@ -0,0 +1,471 @@
+use ft_api::config_env_var;
+use serde::{Deserialize, Deserializer};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::i18n::Locale;
+use crate::server::Transport;
+use crate::timeouts::Timeouts;
+
+const DEFAULT_CONFIG_PATH: &str = "gsctl.toml";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    Invalid {
+        name: String,
+        reason: String,
+    },
+    Missing(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, source } => {
+                write!(f, "could not read config {}: {source}", path.display())
+            }
+            ConfigError::Parse { path, source } => {
+                write!(f, "invalid config {}: {source}", path.display())
+            }
+            ConfigError::Invalid { name, reason } => write!(f, "invalid {name}: {reason}"),
+            ConfigError::Missing(names) => write!(f, "missing config: {}", names.join(", ")),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+pub(crate) fn secs<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    u64::deserialize(deserializer).map(Duration::from_secs)
+}
+
+// Everything the bot reads at startup. Loaded once, then shared read-only.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub slack: SlackConfig,
+    pub ft: FtConfig,
+    pub homemaker: HomemakerConfig,
+    pub ssh: SshConfig,
+    pub timeouts: Timeouts,
+    pub storage: StorageConfig,
+    pub jobs: JobsConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+    pub transport: Transport,
+    #[serde(rename = "shutdown_grace_secs", deserialize_with = "secs")]
+    pub shutdown_grace: Duration,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            transport: Transport::Http,
+            shutdown_grace: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SlackConfig {
+    pub bot_token: String,
+    // Only needed for Socket Mode.
+    pub app_token: Option<String>,
+    // Only needed over HTTP.
+    pub signing_secret: Option<String>,
+    pub client_id: String,
+    pub client_secret: String,
+    pub bot_scope: String,
+    pub redirect_host: String,
+    // How a mention of the bot arrives in the text, `<@U...>`.
+    pub wakeup_word: String,
+    // How students are told to call the bot in help and error messages.
+    pub wakeup_word_for_user: String,
+    pub staff_channel: Option<String>,
+    pub default_locale: Locale,
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FtConfig {
+    // ft-api reads its credentials from the environment itself, so these
+    // two are never taken from the file.
+    #[serde(skip)]
+    pub client_uid: String,
+    #[serde(skip)]
+    pub client_secret: String,
+    pub oauth_redirect_uri: String,
+    #[serde(rename = "token_lifetime_secs", deserialize_with = "secs")]
+    pub token_lifetime: Duration,
+    #[serde(rename = "location_cache_ttl_secs", deserialize_with = "secs")]
+    pub location_cache_ttl: Duration,
+}
+
+impl Default for FtConfig {
+    fn default() -> Self {
+        FtConfig {
+            client_uid: String::new(),
+            client_secret: String::new(),
+            oauth_redirect_uri: String::new(),
+            // 42 intra access tokens are valid for two hours.
+            token_lifetime: Duration::from_secs(2 * 60 * 60),
+            location_cache_ttl: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HomemakerConfig {
+    pub url: String,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SshConfig {
+    pub ansible_cluster_port: u16,
+}
+
+impl Default for SshConfig {
+    fn default() -> Self {
+        SshConfig {
+            ansible_cluster_port: 22,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    pub users: PathBuf,
+    pub jobs: PathBuf,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig {
+            users: "users.json".into(),
+            jobs: "jobs.json".into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JobsConfig {
+    pub max_concurrent: usize,
+    #[serde(rename = "confirm_timeout_secs", deserialize_with = "secs")]
+    pub confirm_timeout: Duration,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        JobsConfig {
+            max_concurrent: 4,
+            confirm_timeout: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+// Sets config fields from env vars that are present.
+struct Env<F>(F);
+
+impl<F: Fn(&str) -> Option<String>> Env<F> {
+    fn string(&self, name: &str, field: &mut String) {
+        if let Some(value) = (self.0)(name) {
+            *field = value;
+        }
+    }
+
+    fn optional(&self, name: &str, field: &mut Option<String>) {
+        if let Some(value) = (self.0)(name) {
+            *field = Some(value);
+        }
+    }
+
+    fn parse<T>(&self, name: &str, field: &mut T) -> Result<(), ConfigError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        if let Some(value) = (self.0)(name) {
+            *field = value.parse().map_err(|err| ConfigError::Invalid {
+                name: name.to_string(),
+                reason: format!("`{value}`: {err}"),
+            })?;
+        }
+        Ok(())
+    }
+
+    fn secs(&self, name: &str, field: &mut Duration) -> Result<(), ConfigError> {
+        let mut secs = field.as_secs();
+        self.parse(name, &mut secs)?;
+        *field = Duration::from_secs(secs);
+        Ok(())
+    }
+}
+
+impl Config {
+    // Defaults, then the TOML file named by `GSCTL_CONFIG` (`gsctl.toml` if
+    // unset, and optional then), then env vars.
+    pub fn load() -> Result<Self, ConfigError> {
+        let var = |name: &str| config_env_var(name).ok().filter(|value| !value.is_empty());
+
+        let mut config = match var("GSCTL_CONFIG") {
+            Some(path) => Config::read(Path::new(&path))?,
+            None => match Config::read(Path::new(DEFAULT_CONFIG_PATH)) {
+                Err(ConfigError::Read { source, .. })
+                    if source.kind() == io::ErrorKind::NotFound =>
+                {
+                    Config::default()
+                }
+                config => config?,
+            },
+        };
+        config.apply_env(var)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn read(path: &Path) -> Result<Self, ConfigError> {
+        let raw = fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.into(),
+            source,
+        })?;
+        toml::from_str(&raw).map_err(|source| ConfigError::Parse {
+            path: path.into(),
+            source,
+        })
+    }
+
+    fn apply_env(&mut self, var: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        let env = Env(var);
+
+        env.parse("BIND_ADDRESS", &mut self.server.bind)?;
+        env.parse("SLACK_TRANSPORT", &mut self.server.transport)?;
+        env.secs("SHUTDOWN_GRACE_SECS", &mut self.server.shutdown_grace)?;
+
+        let slack = &mut self.slack;
+        env.string("SLACK_TOKEN", &mut slack.bot_token);
+        env.optional("SLACK_APP_TOKEN", &mut slack.app_token);
+        env.optional("SLACK_SIGNING_SECRET", &mut slack.signing_secret);
+        env.string("SLACK_CLIENT_ID", &mut slack.client_id);
+        env.string("SLACK_CLIENT_SECRET", &mut slack.client_secret);
+        env.string("SLACK_BOT_SCOPE", &mut slack.bot_scope);
+        env.string("SLACK_REDIRECT_HOST", &mut slack.redirect_host);
+        env.string("WAKEUP_WORD", &mut slack.wakeup_word);
+        env.string("WAKEUP_WORD_FOR_USER", &mut slack.wakeup_word_for_user);
+        env.optional("STAFF_CHANNEL_ID", &mut slack.staff_channel);
+        env.parse("DEFAULT_LOCALE", &mut slack.default_locale)?;
+
+        let ft = &mut self.ft;
+        env.string("FT_API_CLIENT_UID", &mut ft.client_uid);
+        env.string("FT_API_CLIENT_SECRET", &mut ft.client_secret);
+        env.string("FT_OAUTH_REDIRECT_URI", &mut ft.oauth_redirect_uri);
+        env.secs("FT_TOKEN_LIFETIME_SECS", &mut ft.token_lifetime)?;
+        env.secs("LOCATION_CACHE_TTL_SECS", &mut ft.location_cache_ttl)?;
+
+        env.string("STUDENT_STORAGE_API_URL", &mut self.homemaker.url);
+        env.string("HOMEMAKER_SECRET_TOKEN", &mut self.homemaker.token);
+        env.parse(
+            "ANSIBLE_CLUSTER_SSH_PORT",
+            &mut self.ssh.ansible_cluster_port,
+        )?;
+
+        let timeouts = &mut self.timeouts;
+        env.secs("REBOOT_TIMEOUT_SECS", &mut timeouts.reboot)?;
+        env.secs("GOINFRE_RESET_TIMEOUT_SECS", &mut timeouts.goinfre_reset)?;
+        env.secs("COMMAND_TIMEOUT_SECS", &mut timeouts.adhoc)?;
+        env.secs("HOME_CREATE_TIMEOUT_SECS", &mut timeouts.home_create)?;
+        env.secs("HOME_DELETE_TIMEOUT_SECS", &mut timeouts.home_delete)?;
+        env.secs("HOME_CLOSE_TIMEOUT_SECS", &mut timeouts.home_close)?;
+        env.secs("HOME_STATUS_TIMEOUT_SECS", &mut timeouts.home_status)?;
+
+        env.parse("USER_STORE_PATH", &mut self.storage.users)?;
+        env.parse("JOB_STORE_PATH", &mut self.storage.jobs)?;
+        env.parse("MAX_CONCURRENT_JOBS", &mut self.jobs.max_concurrent)?;
+        env.secs("CONFIRM_TIMEOUT_SECS", &mut self.jobs.confirm_timeout)?;
+        Ok(())
+    }
+
+    // Reports every missing value at once, by file key and env var.
+    fn validate(&self) -> Result<(), ConfigError> {
+        let slack = &self.slack;
+        let transport_secret = match self.server.transport {
+            Transport::Http => (
+                "slack.signing_secret (SLACK_SIGNING_SECRET)",
+                &slack.signing_secret,
+            ),
+            Transport::SocketMode => ("slack.app_token (SLACK_APP_TOKEN)", &slack.app_token),
+        };
+        let required = [
+            ("slack.bot_token (SLACK_TOKEN)", slack.bot_token.as_str()),
+            (
+                transport_secret.0,
+                transport_secret.1.as_deref().unwrap_or_default(),
+            ),
+            ("slack.client_id (SLACK_CLIENT_ID)", &slack.client_id),
+            (
+                "slack.client_secret (SLACK_CLIENT_SECRET)",
+                &slack.client_secret,
+            ),
+            ("slack.bot_scope (SLACK_BOT_SCOPE)", &slack.bot_scope),
+            (
+                "slack.redirect_host (SLACK_REDIRECT_HOST)",
+                &slack.redirect_host,
+            ),
+            ("slack.wakeup_word (WAKEUP_WORD)", &slack.wakeup_word),
+            (
+                "slack.wakeup_word_for_user (WAKEUP_WORD_FOR_USER)",
+                &slack.wakeup_word_for_user,
+            ),
+            ("FT_API_CLIENT_UID", &self.ft.client_uid),
+            ("FT_API_CLIENT_SECRET", &self.ft.client_secret),
+            (
+                "ft.oauth_redirect_uri (FT_OAUTH_REDIRECT_URI)",
+                &self.ft.oauth_redirect_uri,
+            ),
+            (
+                "homemaker.url (STUDENT_STORAGE_API_URL)",
+                &self.homemaker.url,
+            ),
+            (
+                "homemaker.token (HOMEMAKER_SECRET_TOKEN)",
+                &self.homemaker.token,
+            ),
+        ];
+        let missing: Vec<String> = required
+            .iter()
+            .filter(|(_, value)| value.trim().is_empty())
+            .map(|(name, _)| name.to_string())
+            .collect();
+        if !missing.is_empty() {
+            return Err(ConfigError::Missing(missing));
+        }
+
+        if self.jobs.max_concurrent == 0 {
+            return Err(ConfigError::Invalid {
+                name: "jobs.max_concurrent (MAX_CONCURRENT_JOBS)".to_string(),
+                reason: "at least one job has to be able to run".to_string(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    const FILE: &str = r#"
+        [server]
+        bind = "0.0.0.0:3000"
+        transport = "socket"
+
+        [slack]
+        bot_token = "xoxb-file"
+        app_token = "xapp-file"
+        client_id = "id"
+        client_secret = "secret"
+        bot_scope = "chat:write"
+        redirect_host = "https://bot.example"
+        wakeup_word = "<@UBOT>"
+        wakeup_word_for_user = "@gsctl"
+        default_locale = "en"
+
+        [ft]
+        oauth_redirect_uri = "https://bot.example/link"
+
+        [homemaker]
+        url = "http://storage/api/"
+        token = "t0ps3cret"
+
+        [timeouts]
+        reboot_secs = 30
+    "#;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn env_overrides_the_file() {
+        let mut config: Config = toml::from_str(FILE).unwrap();
+        config
+            .apply_env(env(&[
+                ("SLACK_TOKEN", "xoxb-env"),
+                ("FT_API_CLIENT_UID", "uid"),
+                ("FT_API_CLIENT_SECRET", "secret"),
+                ("MAX_CONCURRENT_JOBS", "2"),
+            ]))
+            .unwrap();
+        config.validate().unwrap();
+
+        assert_eq!(config.server.bind, SocketAddr::from(([0, 0, 0, 0], 3000)));
+        assert_eq!(config.server.transport, Transport::SocketMode);
+        assert_eq!(config.slack.bot_token, "xoxb-env");
+        assert_eq!(config.slack.default_locale, Locale::En);
+        assert_eq!(config.timeouts.reboot, Duration::from_secs(30));
+        assert_eq!(config.timeouts.adhoc, Timeouts::default().adhoc);
+        assert_eq!(config.ssh.ansible_cluster_port, 22);
+        assert_eq!(config.jobs.max_concurrent, 2);
+    }
+
+    #[test]
+    fn example_config_parses() {
+        let config: Config = toml::from_str(include_str!("../gsctl.example.toml")).unwrap();
+        assert_eq!(config.slack.wakeup_word_for_user, "@gsctl");
+    }
+
+    #[test]
+    fn startup_errors_name_the_setting() {
+        let mut config: Config = toml::from_str(FILE).unwrap();
+        let Err(err) = config.apply_env(env(&[("MAX_CONCURRENT_JOBS", "many")])) else {
+            panic!("a bad number was accepted");
+        };
+        assert!(err.to_string().contains("MAX_CONCURRENT_JOBS"));
+
+        config
+            .apply_env(env(&[("SLACK_TRANSPORT", "http")]))
+            .unwrap();
+        let Err(ConfigError::Missing(missing)) = config.validate() else {
+            panic!("missing credentials were accepted");
+        };
+        assert_eq!(
+            missing,
+            vec![
+                "slack.signing_secret (SLACK_SIGNING_SECRET)",
+                "FT_API_CLIENT_UID",
+                "FT_API_CLIENT_SECRET",
+            ]
+        );
+
+        assert!(toml::from_str::<Config>("[slack]\nwakeup = \"x\"").is_err());
+    }
+}
@@ -0,0 +1,162 @@
+use slack_morphism::SlackUserId;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::bot_cmd::{GsctlCommand, SlackMessageContext};
+
+pub const CONFIRM_ACTION: &str = "gsctl-confirm";
+pub const CANCEL_ACTION: &str = "gsctl-cancel";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ConfirmError {
+    Unknown,
+    Expired,
+    NotRequester,
+}
+
+impl fmt::Display for ConfirmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfirmError::Unknown => write!(f, "unknown confirmation"),
+            ConfirmError::Expired => write!(f, "confirmation expired"),
+            ConfirmError::NotRequester => write!(f, "clicked by someone else"),
+        }
+    }
+}
+
+impl std::error::Error for ConfirmError {}
+
+#[derive(Debug)]
+struct PendingConfirmation {
+    context: SlackMessageContext,
+    command: GsctlCommand,
+    expires_at: Instant,
+}
+
+// Destructive commands wait here until their requester answers the buttons.
+#[derive(Debug)]
+pub struct PendingConfirmations {
+    ttl: Duration,
+    tasks: Mutex<HashMap<String, PendingConfirmation>>,
+}
+
+impl PendingConfirmations {
+    pub fn new(ttl: Duration) -> Self {
+        PendingConfirmations {
+            ttl,
+            tasks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn insert(
+        &self,
+        context: SlackMessageContext,
+        command: GsctlCommand,
+        now: Instant,
+    ) -> String {
+        let id = Uuid::new_v4().simple().to_string();
+        let mut tasks = self.tasks.lock().unwrap();
+
+        tasks.retain(|_, pending| pending.expires_at > now);
+        tasks.insert(
+            id.clone(),
+            PendingConfirmation {
+                context,
+                command,
+                expires_at: now + self.ttl,
+            },
+        );
+        id
+    }
+
+    // Only the requester can answer. Anyone else clicking leaves the
+    // confirmation in place for them.
+    pub fn take(
+        &self,
+        id: &str,
+        user: &SlackUserId,
+        now: Instant,
+    ) -> Result<(SlackMessageContext, GsctlCommand), ConfirmError> {
+        let mut tasks = self.tasks.lock().unwrap();
+        match tasks.get(id) {
+            None => Err(ConfirmError::Unknown),
+            Some(pending) if pending.expires_at <= now => {
+                tasks.remove(id);
+                Err(ConfirmError::Expired)
+            }
+            Some(pending) if &pending.context.user != user => Err(ConfirmError::NotRequester),
+            Some(_) => {
+                let pending = tasks.remove(id).unwrap();
+                Ok((pending.context, pending.command))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot_cmd::{MessageSource, SubCommand};
+    use crate::i18n::Locale;
+    use ft_api::FtLoginId;
+    use slack_morphism::prelude::*;
+
+    fn context(user: &str) -> SlackMessageContext {
+        SlackMessageContext {
+            channel: SlackChannelId("C1".to_string()),
+            source: MessageSource::Mention {
+                ts: SlackTs("1".to_string()),
+                thread_ts: None,
+            },
+            user: SlackUserId(user.to_string()),
+            real_name: "student".to_string(),
+            is_admin: false,
+            locale: Locale::En,
+            text: "home reset".to_string(),
+        }
+    }
+
+    fn reset() -> GsctlCommand {
+        GsctlCommand::Home(Some(SubCommand::Reset(FtLoginId("student".to_string()))))
+    }
+
+    #[test]
+    fn only_the_requester_can_answer_once() {
+        let pending = PendingConfirmations::new(Duration::from_secs(60));
+        let now = Instant::now();
+        let requester = SlackUserId("U1".to_string());
+
+        let id = pending.insert(context("U1"), reset(), now);
+
+        assert_eq!(
+            pending
+                .take(&id, &SlackUserId("U2".to_string()), now)
+                .unwrap_err(),
+            ConfirmError::NotRequester
+        );
+        assert!(pending.take(&id, &requester, now).is_ok());
+        assert_eq!(
+            pending.take(&id, &requester, now).unwrap_err(),
+            ConfirmError::Unknown
+        );
+    }
+
+    #[test]
+    fn confirmation_expires() {
+        let pending = PendingConfirmations::new(Duration::from_secs(60));
+        let now = Instant::now();
+        let requester = SlackUserId("U1".to_string());
+
+        let id = pending.insert(context("U1"), reset(), now);
+
+        assert_eq!(
+            pending
+                .take(&id, &requester, now + Duration::from_secs(60))
+                .unwrap_err(),
+            ConfirmError::Expired
+        );
+    }
+}
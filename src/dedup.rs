@@ -0,0 +1,62 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Slack retries a delivery up to three times over about five minutes.
+const SEEN_TTL: Duration = Duration::from_secs(10 * 60);
+
+// Remembers recently handled events so a retried delivery, or the same
+// message arriving as both a mention and a DM, runs only once.
+#[derive(Debug)]
+pub struct SeenEvents {
+    ttl: Duration,
+    seen: Mutex<HashMap<String, Instant>>,
+}
+
+impl Default for SeenEvents {
+    fn default() -> Self {
+        SeenEvents::new(SEEN_TTL)
+    }
+}
+
+impl SeenEvents {
+    pub fn new(ttl: Duration) -> Self {
+        SeenEvents {
+            ttl,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // True if none of `keys` was seen within the ttl. All of them are
+    // remembered either way.
+    pub fn first_time(&self, keys: &[String], now: Instant) -> bool {
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, at| now.duration_since(*at) < self.ttl);
+
+        let mut new = true;
+        for key in keys {
+            new &= seen.insert(key.clone(), now).is_none();
+        }
+        new
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn any_known_key_marks_a_duplicate() {
+        let seen = SeenEvents::new(Duration::from_secs(60));
+        let now = Instant::now();
+
+        assert!(seen.first_time(&["Ev1".into(), "C1:1.0".into()], now));
+        assert!(!seen.first_time(&["Ev1".into(), "C1:1.0".into()], now));
+        assert!(!seen.first_time(&["Ev2".into(), "C1:1.0".into()], now));
+        assert!(seen.first_time(&["Ev3".into(), "C1:2.0".into()], now));
+        assert!(seen.first_time(
+            &["Ev1".into(), "C1:1.0".into()],
+            now + Duration::from_secs(60)
+        ));
+    }
+}
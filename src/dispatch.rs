@@ -0,0 +1,291 @@
+use std::io;
+use std::sync::Arc;
+
+use tracing::debug;
+
+use crate::{
+    bot_cmd::{GsctlCommand, SubCommand},
+    error::{Action, BotError},
+    excutor::{ExecOutput, Executor, RawCommand, Target},
+    home_reset::{reset_home, ResetOutcome},
+    homemaker::{HomemakerError, HomesApi},
+    i18n::{tr, Locale},
+    staff::StaffNotifier,
+};
+
+#[derive(Clone)]
+pub struct Backends {
+    pub executor: Arc<dyn Executor>,
+    pub homes: Arc<dyn HomesApi>,
+    pub staff: Arc<dyn StaffNotifier>,
+    pub staff_locale: Locale,
+}
+
+fn exec_result(action: Action, output: io::Result<ExecOutput>) -> Result<Option<String>, BotError> {
+    match output {
+        Ok(output) if output.success() => {
+            debug!(
+                "{} done in {:?}: {}",
+                action.name(),
+                output.duration,
+                output.stdout
+            );
+            Ok(None)
+        }
+        Ok(output) => Err(BotError::Failed {
+            action,
+            detail: format!(
+                "exit code {:?}: {} {}",
+                output.exit_code, output.stdout, output.stderr
+            ),
+        }),
+        Err(err) if err.kind() == io::ErrorKind::TimedOut => Err(BotError::Timeout(action)),
+        Err(err) => Err(BotError::Failed {
+            action,
+            detail: format!("could not run: {err}"),
+        }),
+    }
+}
+
+fn homes_error(action: Action, err: HomemakerError) -> BotError {
+    match err {
+        HomemakerError::Timeout => BotError::Timeout(action),
+        err => BotError::Failed {
+            action,
+            detail: err.to_string(),
+        },
+    }
+}
+
+pub async fn run_command(
+    command: GsctlCommand,
+    backends: &Backends,
+) -> Result<Option<String>, BotError> {
+    match command {
+        GsctlCommand::Reboot(location) => {
+            let output = backends
+                .executor
+                .execute(
+                    Target::AnsibleCluster,
+                    RawCommand::build_pc_reboot(&location),
+                )
+                .await;
+
+            exec_result(Action::Reboot, output)
+        }
+        GsctlCommand::Home(Some(SubCommand::Reset(login))) => {
+            match reset_home(backends.homes.as_ref(), &login).await {
+                ResetOutcome::Done { completed } => {
+                    debug!("Home reset of {login} done: {completed:?}");
+                    Ok(None)
+                }
+                ResetOutcome::Aborted { failed, error } => {
+                    debug!("Home reset of {login} stopped at {failed}, nothing changed.");
+                    Err(homes_error(Action::HomeReset, error))
+                }
+                ResetOutcome::Partial {
+                    completed,
+                    failed,
+                    error,
+                    snapshot,
+                } => {
+                    let detail = format!("{login} at {failed} after {completed:?}: {error}");
+                    let completed = completed
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    let state = snapshot.map(|s| s.state).unwrap_or_else(|| "-".to_string());
+                    backends
+                        .staff
+                        .notify(tr(
+                            backends.staff_locale,
+                            "staff.home_reset_partial",
+                            &[
+                                ("login", &login.0),
+                                ("step", &failed.to_string()),
+                                ("completed", &completed),
+                                ("state", &state),
+                                ("error", &error.to_string()),
+                            ],
+                        ))
+                        .await;
+                    Err(BotError::HomeResetPartial(detail))
+                }
+                ResetOutcome::Unverified { completed, error } => {
+                    let detail = format!("{login} after {completed:?}: {error}");
+                    backends
+                        .staff
+                        .notify(tr(
+                            backends.staff_locale,
+                            "staff.home_reset_unverified",
+                            &[("login", &login.0), ("error", &error.to_string())],
+                        ))
+                        .await;
+                    Err(BotError::HomeResetUnverified(detail))
+                }
+            }
+        }
+        GsctlCommand::Home(Some(SubCommand::Close(login, location))) => {
+            match backends.homes.close(&login, &location).await {
+                Ok(()) => {
+                    debug!("Home close on {location}, login: {login} done");
+                    Ok(None)
+                }
+                Err(err) => Err(homes_error(Action::HomeClose, err)),
+            }
+        }
+        GsctlCommand::Goinfre(Some(SubCommand::ResetAt(login, location))) => {
+            let output = backends
+                .executor
+                .execute(
+                    Target::AnsibleCluster,
+                    RawCommand::build_goinfre_reset(&location, &login),
+                )
+                .await;
+
+            exec_result(Action::GoinfreReset, output)
+        }
+        // `update` and `link` talk to Slack and are handled by the server loop.
+        GsctlCommand::Update => Err(BotError::NotImplemented(Action::Update.name().into())),
+        GsctlCommand::Link => Err(BotError::NotImplemented(Action::Link.name().into())),
+        GsctlCommand::Home(_) => Err(BotError::NotImplemented("home".into())),
+        GsctlCommand::Goinfre(_) => Err(BotError::NotImplemented("goinfre".into())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::excutor::ScriptedBackend;
+    use crate::homemaker::{HomeStatus, ScriptedHomes};
+    use crate::staff::RecordingNotifier;
+    use ft_api::{FtHost, FtLoginId};
+
+    fn backends(executor: Arc<ScriptedBackend>, homes: Arc<ScriptedHomes>) -> Backends {
+        Backends {
+            executor,
+            homes,
+            staff: Arc::new(RecordingNotifier::new()),
+            staff_locale: Locale::En,
+        }
+    }
+
+    #[tokio::test]
+    async fn reboot_runs_playbook_on_ansible_cluster() {
+        let executor = Arc::new(ScriptedBackend::new());
+        executor.push_exit(0, "ok", "");
+        let backends = backends(executor.clone(), Arc::new(ScriptedHomes::new()));
+
+        let result = run_command(
+            GsctlCommand::Reboot(FtHost("c1r1s1".to_string())),
+            &backends,
+        )
+        .await;
+
+        assert!(matches!(result, Ok(None)));
+        assert_eq!(
+            executor.calls(),
+            vec![(
+                Target::AnsibleCluster,
+                "ansible-playbook '-l' 'c1r1s1' 'reboot.yml'".to_string()
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_goinfre_reset_is_reported() {
+        let executor = Arc::new(ScriptedBackend::new());
+        executor.push_exit(2, "", "unreachable");
+        let backends = backends(executor, Arc::new(ScriptedHomes::new()));
+
+        let result = run_command(
+            GsctlCommand::Goinfre(Some(SubCommand::ResetAt(
+                FtLoginId("student".to_string()),
+                FtHost("c1r1s1".to_string()),
+            ))),
+            &backends,
+        )
+        .await;
+
+        assert_eq!(
+            result.unwrap_err().message(Locale::En, "@gsctl"),
+            "Goinfre reset failed."
+        );
+    }
+
+    #[tokio::test]
+    async fn timed_out_reboot_is_reported() {
+        let executor = Arc::new(ScriptedBackend::new());
+        executor.push(Err(io::ErrorKind::TimedOut.into()));
+        let backends = backends(executor, Arc::new(ScriptedHomes::new()));
+
+        let result = run_command(
+            GsctlCommand::Reboot(FtHost("c1r1s1".to_string())),
+            &backends,
+        )
+        .await;
+
+        assert_eq!(
+            result.unwrap_err().message(Locale::En, "@gsctl"),
+            "`reboot` did not finish in time. Please try again later or contact staff."
+        );
+    }
+
+    #[tokio::test]
+    async fn home_close_goes_through_homes_api() {
+        let homes = Arc::new(ScriptedHomes::new());
+        homes.push_err(HomemakerError::Unauthorized);
+        let backends = backends(Arc::new(ScriptedBackend::new()), homes.clone());
+
+        let result = run_command(
+            GsctlCommand::Home(Some(SubCommand::Close(
+                FtLoginId("student".to_string()),
+                "iqn.fr.42:c1r1s1".to_string(),
+            ))),
+            &backends,
+        )
+        .await;
+
+        assert_eq!(
+            result.unwrap_err().message(Locale::En, "@gsctl"),
+            "Home close failed."
+        );
+        assert_eq!(homes.calls(), vec!["close student iqn.fr.42:c1r1s1"]);
+    }
+
+    #[tokio::test]
+    async fn partial_home_reset_alerts_staff() {
+        let homes = Arc::new(ScriptedHomes::new());
+        homes
+            .push_status(HomeStatus {
+                state: "open".to_string(),
+                initiator: None,
+            })
+            .push_ok()
+            .push_err(HomemakerError::Unauthorized)
+            .push_err(HomemakerError::Unauthorized);
+        let staff = Arc::new(RecordingNotifier::new());
+        let backends = Backends {
+            executor: Arc::new(ScriptedBackend::new()),
+            homes: homes.clone(),
+            staff: staff.clone(),
+            staff_locale: Locale::En,
+        };
+
+        let result = run_command(
+            GsctlCommand::Home(Some(SubCommand::Reset(FtLoginId("student".to_string())))),
+            &backends,
+        )
+        .await;
+
+        assert_eq!(
+            result.unwrap_err().message(Locale::En, "@gsctl"),
+            "Your home was deleted but could not be recreated. Staff have been notified."
+        );
+        let messages = staff.messages();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("student"));
+        assert!(messages[0].contains("create"));
+    }
+}
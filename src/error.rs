@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io;
+use tracing::{debug, error, warn};
+
+use crate::bot_cmd::help_text;
+use crate::grammar::ParseError;
+use crate::i18n::{tr, Locale};
+use crate::location::LocationError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Reboot,
+    HomeReset,
+    HomeClose,
+    GoinfreReset,
+    Update,
+    Link,
+}
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Reboot => "reboot",
+            Action::HomeReset => "home reset",
+            Action::HomeClose => "home close",
+            Action::GoinfreReset => "goinfre reset",
+            Action::Update => "update",
+            Action::Link => "link",
+        }
+    }
+
+    fn failed_key(self) -> &'static str {
+        match self {
+            Action::Reboot => "reboot.failed",
+            Action::HomeReset => "home.reset.failed",
+            Action::HomeClose => "home.close.failed",
+            Action::GoinfreReset => "goinfre.reset.failed",
+            Action::Update => "update.member_list_failed",
+            Action::Link => "link.dm_failed",
+        }
+    }
+}
+
+// Everything that can go wrong between a Slack message and its reply. The
+// user only ever sees `message`; the details go to the log for staff.
+#[derive(Debug)]
+pub enum BotError {
+    Parse(ParseError),
+    NotRegistered,
+    NotLinked,
+    StaffOnly(Action),
+    NotImplemented(String),
+    Location(LocationError),
+    Timeout(Action),
+    Failed { action: Action, detail: String },
+    HomeResetPartial(String),
+    HomeResetUnverified(String),
+    Store(io::Error),
+}
+
+impl fmt::Display for BotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BotError::Parse(err) => write!(f, "parse error: {err}"),
+            BotError::NotRegistered => write!(f, "user has no 42 login"),
+            BotError::NotLinked => write!(f, "user has no verified 42 login"),
+            BotError::StaffOnly(action) => write!(f, "`{}` is staff only", action.name()),
+            BotError::NotImplemented(command) => write!(f, "`{command}` is not implemented"),
+            BotError::Location(err) => write!(f, "{err}"),
+            BotError::Timeout(action) => write!(f, "`{}` timed out", action.name()),
+            BotError::Failed { action, detail } => {
+                write!(f, "`{}` failed: {detail}", action.name())
+            }
+            BotError::HomeResetPartial(detail) => write!(f, "home reset stopped halfway: {detail}"),
+            BotError::HomeResetUnverified(detail) => {
+                write!(f, "home reset could not be verified: {detail}")
+            }
+            BotError::Store(err) => write!(f, "user store error: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for BotError {}
+
+impl From<ParseError> for BotError {
+    fn from(err: ParseError) -> Self {
+        BotError::Parse(err)
+    }
+}
+
+impl From<LocationError> for BotError {
+    fn from(err: LocationError) -> Self {
+        BotError::Location(err)
+    }
+}
+
+impl BotError {
+    pub fn message(&self, locale: Locale, wakeup_word_for_user: &str) -> String {
+        let link_command = format!("{wakeup_word_for_user} link");
+        let rejected = |reason: String| tr(locale, "error.command_failed", &[("reason", &reason)]);
+
+        match self {
+            BotError::Parse(err) => format!(
+                "{}\n```{}```",
+                err.message(locale),
+                help_text(locale, wakeup_word_for_user, err.path())
+            ),
+            BotError::NotRegistered => rejected(tr(
+                locale,
+                "error.not_registered",
+                &[("command", &link_command)],
+            )),
+            BotError::NotLinked => rejected(tr(
+                locale,
+                "error.not_linked",
+                &[("command", &link_command)],
+            )),
+            BotError::StaffOnly(Action::Reboot) => {
+                rejected(tr(locale, "error.staff_only_reboot", &[]))
+            }
+            BotError::StaffOnly(_) => rejected(tr(locale, "error.staff_only_update", &[])),
+            BotError::NotImplemented(command) => {
+                rejected(tr(locale, "error.not_implemented", &[("command", command)]))
+            }
+            BotError::Location(err) => rejected(err.message(locale)),
+            BotError::Timeout(action) => tr(locale, "error.timeout", &[("command", action.name())]),
+            BotError::Failed { action, .. } => tr(locale, action.failed_key(), &[]),
+            BotError::HomeResetPartial(_) => tr(locale, "home.reset.partial", &[]),
+            BotError::HomeResetUnverified(_) => tr(locale, "home.reset.unverified", &[]),
+            BotError::Store(_) => tr(locale, "update.store_failed", &[]),
+        }
+    }
+
+    // Staff-facing entry. Mistakes by the user are debug noise, anything
+    // that points at broken infrastructure is logged loud enough to notice.
+    pub fn log(&self, request: &str) {
+        match self {
+            BotError::Parse(_)
+            | BotError::NotRegistered
+            | BotError::NotLinked
+            | BotError::StaffOnly(_)
+            | BotError::NotImplemented(_)
+            | BotError::Location(LocationError::NotLoggedIn(_))
+            | BotError::Location(LocationError::Ambiguous(..)) => {
+                debug!("`{request}` rejected: {self}")
+            }
+            BotError::Location(LocationError::ApiUnavailable(_))
+            | BotError::Timeout(_)
+            | BotError::HomeResetUnverified(_) => {
+                warn!("`{request}`: {self}")
+            }
+            BotError::Failed { .. } | BotError::HomeResetPartial(_) | BotError::Store(_) => {
+                error!("`{request}`: {self}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejections_point_at_the_fix() {
+        let message = BotError::NotLinked.message(Locale::En, "@gsctl");
+
+        assert!(message.starts_with("Command cannot be executed"));
+        assert!(message.contains("@gsctl link"));
+    }
+
+    #[test]
+    fn failure_details_stay_out_of_the_reply() {
+        let err = BotError::Failed {
+            action: Action::Reboot,
+            detail: "exit code Some(4): ssh: connect to host 10.0.0.1".to_string(),
+        };
+
+        assert_eq!(err.message(Locale::En, "@gsctl"), "Reboot failed.");
+        assert!(err.to_string().contains("10.0.0.1"));
+    }
+}
@@ -1,104 +1,142 @@
 use ft_api::{FtHost, FtLoginId};
 use rsb_derive::Builder;
+use std::borrow::Cow;
+#[cfg(test)]
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
 use std::io;
-use std::process::Output;
+use std::pin::Pin;
+use std::process::{Output, Stdio};
+#[cfg(test)]
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tokio::process::Command;
 use tracing::*;
 
+use crate::timeouts::Timeouts;
+
+const SSH_CONNECT_TIMEOUT_SECS: u64 = 10;
+// How long a remote command gets to exit after SIGTERM before it is killed.
+const REMOTE_KILL_AFTER_SECS: u64 = 10;
+// Exit status of `timeout` when it had to stop the command.
+const REMOTE_TIMED_OUT: i32 = 124;
+
 #[derive(Debug, Builder)]
 pub struct SshExcutor<'b, 'r> {
     pub ssh_pub_key: Option<&'b str>,
     address: &'b str,
     pub port: Option<u16>,
     pub remote_cmd: Option<RawCommand<'r>>,
+    pub timeout: Option<Duration>,
 }
 
-#[derive(Debug, Builder)]
+#[derive(Builder)]
 pub struct RawCommand<'a> {
     cmd: &'a str,
-    args: Vec<&'a str>,
+    args: Vec<Cow<'a, str>>,
+    #[default = "CommandKind::Adhoc"]
+    pub kind: CommandKind,
+    pub timeout: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandKind {
+    Reboot,
+    GoinfreReset,
+    Adhoc,
 }
 
 impl<'a> RawCommand<'a> {
     pub fn build_pc_reboot(location_hostname: &'a FtHost) -> Self {
-        RawCommand {
-            cmd: "ansible-playbook",
-            args: vec!["-l", location_hostname.0.as_str(), "reboot.yml"],
-        }
+        RawCommand::new(
+            "ansible-playbook",
+            vec![
+                "-l".into(),
+                location_hostname.0.as_str().into(),
+                "reboot.yml".into(),
+            ],
+        )
+        .with_kind(CommandKind::Reboot)
     }
 
-    pub fn build_home_create(login: &'a FtLoginId, url: &'a str, secret: &'a str) -> Self {
-        RawCommand {
-            cmd: "homemakerctl",
-            args: vec![
-                "--url",
-                url,
-                "-t",
-                secret,
-                "homes",
-                "-i",
-                login.0.as_str(),
-                "create",
+    pub fn build_goinfre_reset(location_hostname: &'a FtHost, login: &'a FtLoginId) -> Self {
+        RawCommand::new(
+            "ansible-playbook",
+            vec![
+                "-l".into(),
+                location_hostname.0.as_str().into(),
+                // JSON rather than `login=...`, which Ansible's key=value
+                // parser would split on whitespace.
+                "-e".into(),
+                serde_json::json!({ "login": login.0 }).to_string().into(),
+                "goinfre_reset.yml".into(),
             ],
-        }
+        )
+        .with_kind(CommandKind::GoinfreReset)
     }
 
-    pub fn build_home_delete(login: &'a FtLoginId, url: &'a str, secret: &'a str) -> Self {
-        RawCommand {
-            cmd: "homemakerctl",
-            args: vec![
-                "--url",
-                url,
-                "-t",
-                secret,
-                "homes",
-                "-i",
-                login.0.as_str(),
-                "delete",
-            ],
+    pub fn into_string(self) -> String {
+        let argv = std::iter::once(self.cmd)
+            .chain(self.args.iter().map(|arg| arg.as_ref()))
+            .map(shell_quote)
+            .collect::<Vec<_>>()
+            .join(" ");
+        // Killing the local ssh does not stop the remote command, so it is
+        // bounded on the remote host as well.
+        match self.timeout {
+            Some(timeout) => format!(
+                "timeout --signal=TERM --kill-after={REMOTE_KILL_AFTER_SECS} {} {argv}",
+                timeout.as_secs().max(1)
+            ),
+            None => argv,
         }
     }
+}
 
-    pub fn build_home_close(
-        login: &'a FtLoginId,
-        location_hostname: &'a String,
-        url: &'a str,
-        secret: &'a str,
-    ) -> Self {
-        RawCommand {
-            cmd: "homemakerctl",
-            args: vec![
-                "--url",
-                url,
-                "-t",
-                secret,
-                "homes",
-                "-i",
-                login.0.as_str(),
-                "-q",
-                location_hostname,
-                "-f",
-                "close",
-            ],
+impl fmt::Display for RawCommand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.cmd)?;
+        for arg in &self.args {
+            write!(f, " {}", shell_quote(arg))?;
         }
+        Ok(())
     }
+}
 
-    pub fn into_string(self) -> String {
-        format!("{} {}", self.cmd, self.args.join(" ").as_str())
+impl fmt::Debug for RawCommand<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RawCommand")
+            .field(&format_args!("{self}"))
+            .finish()
     }
 }
 
+pub fn shell_quote(arg: &str) -> String {
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+pub fn as_root(remote_cmd: RawCommand) -> String {
+    format!(
+        "sudo su -l root -c {}",
+        shell_quote(&remote_cmd.into_string())
+    )
+}
+
 impl<'b, 'r> SshExcutor<'b, 'r> {
     pub fn new_ansible_cluster() -> Self {
         SshExcutor::new("ansible@ansiblecluster")
     }
 
-    pub fn new_student_storage() -> Self {
-        SshExcutor::new("root@student-storage")
-    }
-
     pub async fn execute(self) -> io::Result<Output> {
         let mut command = Command::new("ssh");
+        // Fail at once on a password or host key prompt instead of waiting
+        // for the timeout.
+        command
+            .arg("-o")
+            .arg("BatchMode=yes")
+            .arg("-o")
+            .arg(format!("ConnectTimeout={SSH_CONNECT_TIMEOUT_SECS}"));
 
         if let Some(key) = self.ssh_pub_key {
             command.arg("-i").arg(key);
@@ -114,12 +152,275 @@ impl<'b, 'r> SshExcutor<'b, 'r> {
 
         command.arg(self.address);
 
-        if let Some(remote_cmd) = self.remote_cmd {
-            let args = format!("sudo su -l root -c \"{}\"", remote_cmd.into_string());
-            debug!("{}", args);
-            command.arg(args);
+        let mut remote_timeout = None;
+        if let Some(mut remote_cmd) = self.remote_cmd {
+            remote_cmd.timeout = remote_cmd.timeout.or(self.timeout);
+            remote_timeout = remote_cmd.timeout;
+            debug!("{}: {}", self.address, remote_cmd);
+            command.arg(as_root(remote_cmd));
         }
 
-        command.output().await
+        // The local wait outlasts the remote one, so the command is over on
+        // the host before the job lets go of it.
+        let local_timeout = match remote_timeout {
+            Some(timeout) => Some(
+                timeout + Duration::from_secs(REMOTE_KILL_AFTER_SECS + SSH_CONNECT_TIMEOUT_SECS),
+            ),
+            None => self.timeout,
+        };
+        let output = output_with_timeout(command, local_timeout).await?;
+        match remote_timeout {
+            Some(timeout) if output.status.code() == Some(REMOTE_TIMED_OUT) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("timed out after {timeout:?} on {}", self.address),
+            )),
+            _ => Ok(output),
+        }
+    }
+}
+
+pub async fn output_with_timeout(
+    mut command: Command,
+    timeout: Option<Duration>,
+) -> io::Result<Output> {
+    let output = command
+        .kill_on_drop(true)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output();
+
+    match timeout {
+        // Dropping the timed out future drops the child, which kills it.
+        Some(timeout) => tokio::time::timeout(timeout, output).await.map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("timed out after {timeout:?}"),
+            )
+        })?,
+        None => output.await,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    AnsibleCluster,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecOutput {
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration: Duration,
+}
+
+impl ExecOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+pub type ExecFuture<'a> = Pin<Box<dyn Future<Output = io::Result<ExecOutput>> + Send + 'a>>;
+
+pub trait Executor: Send + Sync {
+    fn execute<'a>(&'a self, target: Target, remote_cmd: RawCommand<'a>) -> ExecFuture<'a>;
+}
+
+#[derive(Debug, Clone)]
+pub struct SshBackend {
+    pub ansible_cluster_port: u16,
+    pub timeouts: Timeouts,
+}
+
+impl Executor for SshBackend {
+    fn execute<'a>(&'a self, target: Target, remote_cmd: RawCommand<'a>) -> ExecFuture<'a> {
+        Box::pin(async move {
+            let excutor = match target {
+                Target::AnsibleCluster => {
+                    SshExcutor::new_ansible_cluster().with_port(self.ansible_cluster_port)
+                }
+            };
+
+            let started = Instant::now();
+            let output = excutor
+                .with_timeout(self.timeouts.command(remote_cmd.kind))
+                .with_remote_cmd(remote_cmd)
+                .execute()
+                .await?;
+
+            Ok(ExecOutput {
+                exit_code: output.status.code(),
+                stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+                duration: started.elapsed(),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct ScriptedBackend {
+    script: Mutex<VecDeque<io::Result<ExecOutput>>>,
+    calls: Mutex<Vec<(Target, String)>>,
+}
+
+#[cfg(test)]
+impl ScriptedBackend {
+    pub fn new() -> Self {
+        ScriptedBackend::default()
+    }
+
+    pub fn push(&self, result: io::Result<ExecOutput>) -> &Self {
+        self.script.lock().unwrap().push_back(result);
+        self
+    }
+
+    pub fn push_exit(&self, exit_code: i32, stdout: &str, stderr: &str) -> &Self {
+        self.push(Ok(ExecOutput {
+            exit_code: Some(exit_code),
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+            duration: Duration::ZERO,
+        }))
+    }
+
+    pub fn calls(&self) -> Vec<(Target, String)> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl Executor for ScriptedBackend {
+    fn execute<'a>(&'a self, target: Target, remote_cmd: RawCommand<'a>) -> ExecFuture<'a> {
+        self.calls
+            .lock()
+            .unwrap()
+            .push((target, remote_cmd.to_string()));
+        let result = self.script.lock().unwrap().pop_front().unwrap_or_else(|| {
+            Err(io::Error::other(format!(
+                "no scripted result for {remote_cmd}"
+            )))
+        });
+
+        Box::pin(async move { result })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::process::Command as StdCommand;
+
+    fn run_sh(script: &str) -> Vec<String> {
+        let output = StdCommand::new("sh")
+            .arg("-c")
+            .arg(script)
+            .output()
+            .unwrap();
+        assert!(output.status.success(), "{script}");
+
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .split_terminator('\0')
+            .map(str::to_string)
+            .collect()
+    }
+
+    fn printf_command(args: &[String]) -> RawCommand<'_> {
+        RawCommand::new(
+            "printf",
+            std::iter::once("%s\\0".into())
+                .chain(args.iter().map(|arg| arg.as_str().into()))
+                .collect(),
+        )
+    }
+
+    fn hostile_arg() -> impl Strategy<Value = String> {
+        prop_oneof!["[ '\"`$;&|<>(){}*?!#~\\\\\n\ta-z-]{0,16}", "[^\\x00]{0,16}",]
+    }
+
+    #[tokio::test]
+    async fn output_with_timeout_kills_hung_command() {
+        let mut command = Command::new("sleep");
+        command.arg("30");
+        let started = Instant::now();
+
+        let err = output_with_timeout(command, Some(Duration::from_millis(100)))
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn shell_quote_escapes_single_quote() {
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+
+    #[test]
+    fn as_root_keeps_hostile_login_in_one_argument() {
+        let login = FtLoginId("x'; rm -rf /; echo '".to_string());
+        let host = FtHost("c1r1s1".to_string());
+        let inner = RawCommand::build_goinfre_reset(&host, &login).into_string();
+
+        assert_eq!(
+            inner,
+            r#"'ansible-playbook' '-l' 'c1r1s1' '-e' '{"login":"x'\''; rm -rf /; echo '\''"}' 'goinfre_reset.yml'"#
+        );
+        assert_eq!(
+            as_root(RawCommand::build_goinfre_reset(&host, &login)),
+            format!("sudo su -l root -c {}", shell_quote(&inner))
+        );
+    }
+
+    #[test]
+    fn hostile_login_stays_one_extra_var() {
+        let login = FtLoginId("x ansible_become_user=evil 'y=1' \"z=2\"".to_string());
+        let host = FtHost("c1r1s1".to_string());
+        let script = RawCommand::build_goinfre_reset(&host, &login)
+            .into_string()
+            .replacen("'ansible-playbook'", "printf '%s\\0'", 1);
+
+        let argv = run_sh(&script);
+        let extra_vars: serde_json::Value = serde_json::from_str(&argv[3]).unwrap();
+        assert_eq!(argv[2], "-e");
+        assert_eq!(extra_vars, serde_json::json!({ "login": login.0 }));
+    }
+
+    #[test]
+    fn timeout_stops_the_remote_command() {
+        let remote_cmd =
+            RawCommand::new("sleep", vec!["30".into()]).with_timeout(Duration::from_secs(1));
+        let started = Instant::now();
+
+        let status = StdCommand::new("sh")
+            .arg("-c")
+            .arg(remote_cmd.into_string())
+            .status()
+            .unwrap();
+
+        assert_eq!(status.code(), Some(REMOTE_TIMED_OUT));
+        assert!(started.elapsed() < Duration::from_secs(10));
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn into_string_round_trips_through_sh(args in prop::collection::vec(hostile_arg(), 1..6)) {
+            prop_assert_eq!(run_sh(&printf_command(&args).into_string()), args);
+        }
+
+        #[test]
+        fn nested_shell_round_trips(args in prop::collection::vec(hostile_arg(), 1..6)) {
+            let script = format!("sh -c {}", shell_quote(&printf_command(&args).into_string()));
+
+            prop_assert_eq!(run_sh(&script), args);
+        }
     }
 }
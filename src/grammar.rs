@@ -0,0 +1,486 @@
+use std::fmt;
+
+use crate::i18n::{tr, Locale};
+
+#[derive(Debug)]
+pub struct CommandNode {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub args: &'static [ArgSpec],
+    pub flags: &'static [FlagSpec],
+    pub subcommands: &'static [CommandNode],
+}
+
+#[derive(Debug)]
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub help: &'static str,
+    pub required: bool,
+    pub validate: Option<fn(&str) -> bool>,
+}
+
+#[derive(Debug)]
+pub struct FlagSpec {
+    pub long: &'static str,
+    pub help: &'static str,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParsedCommand<'t> {
+    pub path: Vec<&'static str>,
+    pub args: Vec<(&'static str, &'t str)>,
+    pub flags: Vec<&'static str>,
+    pub help: bool,
+}
+
+const HELP_COMMAND: &str = "help";
+const HELP_FLAGS: [&str; 2] = ["-h", "--help"];
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    MissingCommand {
+        path: Vec<&'static str>,
+    },
+    UnknownCommand {
+        path: Vec<&'static str>,
+        token: String,
+    },
+    UnknownFlag {
+        path: Vec<&'static str>,
+        token: String,
+    },
+    UnexpectedArgument {
+        path: Vec<&'static str>,
+        token: String,
+    },
+    MissingArgument {
+        path: Vec<&'static str>,
+        arg: &'static str,
+    },
+    InvalidArgument {
+        path: Vec<&'static str>,
+        arg: &'static str,
+        token: String,
+    },
+}
+
+impl<'t> ParsedCommand<'t> {
+    pub fn arg(&self, name: &str) -> Option<&'t str> {
+        self.args
+            .iter()
+            .find(|(arg, _)| *arg == name)
+            .map(|(_, value)| *value)
+    }
+
+    pub fn has_flag(&self, long: &str) -> bool {
+        self.flags.contains(&long)
+    }
+}
+
+impl ParseError {
+    pub fn path(&self) -> &[&'static str] {
+        match self {
+            ParseError::MissingCommand { path }
+            | ParseError::UnknownCommand { path, .. }
+            | ParseError::UnknownFlag { path, .. }
+            | ParseError::UnexpectedArgument { path, .. }
+            | ParseError::MissingArgument { path, .. }
+            | ParseError::InvalidArgument { path, .. } => path,
+        }
+    }
+}
+
+impl ParseError {
+    pub fn message(&self, locale: Locale) -> String {
+        let path = self.path().join(" ");
+
+        match self {
+            ParseError::MissingCommand { path } if path.is_empty() => {
+                tr(locale, "parse.missing_command", &[])
+            }
+            ParseError::MissingCommand { .. } => {
+                tr(locale, "parse.missing_subcommand", &[("path", &path)])
+            }
+            ParseError::UnknownCommand { path, token } if path.is_empty() => {
+                tr(locale, "parse.unknown_command", &[("token", token)])
+            }
+            ParseError::UnknownCommand { token, .. } => tr(
+                locale,
+                "parse.unknown_subcommand",
+                &[("token", token), ("path", &path)],
+            ),
+            ParseError::UnknownFlag { token, .. } => tr(
+                locale,
+                "parse.unknown_flag",
+                &[("token", token), ("path", &path)],
+            ),
+            ParseError::UnexpectedArgument { token, .. } => tr(
+                locale,
+                "parse.unexpected_argument",
+                &[("token", token), ("path", &path)],
+            ),
+            ParseError::MissingArgument { arg, .. } => tr(
+                locale,
+                "parse.missing_argument",
+                &[("arg", arg), ("path", &path)],
+            ),
+            ParseError::InvalidArgument { arg, token, .. } => tr(
+                locale,
+                "parse.invalid_argument",
+                &[("token", token), ("arg", arg), ("path", &path)],
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message(Locale::En))
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl CommandNode {
+    pub fn find(&self, name: &str) -> Option<&CommandNode> {
+        self.subcommands.iter().find(|node| node.name == name)
+    }
+
+    pub fn parse<'t>(&self, text: &'t str) -> Result<ParsedCommand<'t>, ParseError> {
+        self.parse_tokens(text.split_whitespace())
+    }
+
+    pub fn parse_tokens<'t, I>(&self, tokens: I) -> Result<ParsedCommand<'t>, ParseError>
+    where
+        I: IntoIterator<Item = &'t str>,
+    {
+        let mut tokens = tokens.into_iter().peekable();
+        let mut node = self;
+        let mut parsed = ParsedCommand {
+            path: vec![],
+            args: vec![],
+            flags: vec![],
+            help: false,
+        };
+
+        while !node.subcommands.is_empty() {
+            match tokens.peek() {
+                Some(token) if *token == HELP_COMMAND || HELP_FLAGS.contains(token) => {
+                    parsed.help = true;
+                    return Ok(parsed);
+                }
+                Some(token) if token.starts_with("--") => {
+                    parsed.flags.push(node.parse_flag(token, &parsed.path)?);
+                    tokens.next();
+                }
+                Some(token) => match node.find(token) {
+                    Some(child) => {
+                        parsed.path.push(child.name);
+                        node = child;
+                        tokens.next();
+                    }
+                    None => {
+                        return Err(ParseError::UnknownCommand {
+                            path: parsed.path,
+                            token: token.to_string(),
+                        })
+                    }
+                },
+                None => return Err(ParseError::MissingCommand { path: parsed.path }),
+            }
+        }
+
+        let mut specs = node.args.iter();
+        for token in tokens {
+            if HELP_FLAGS.contains(&token) {
+                parsed.help = true;
+                return Ok(parsed);
+            }
+
+            if token.starts_with("--") {
+                parsed.flags.push(node.parse_flag(token, &parsed.path)?);
+                continue;
+            }
+
+            match specs.next() {
+                Some(spec) if spec.validate.is_none_or(|validate| validate(token)) => {
+                    parsed.args.push((spec.name, token));
+                }
+                Some(spec) => {
+                    return Err(ParseError::InvalidArgument {
+                        path: parsed.path,
+                        arg: spec.name,
+                        token: token.to_string(),
+                    })
+                }
+                None => {
+                    return Err(ParseError::UnexpectedArgument {
+                        path: parsed.path,
+                        token: token.to_string(),
+                    })
+                }
+            }
+        }
+
+        if let Some(spec) = specs.find(|spec| spec.required) {
+            return Err(ParseError::MissingArgument {
+                path: parsed.path,
+                arg: spec.name,
+            });
+        }
+
+        Ok(parsed)
+    }
+
+    pub fn resolve(&self, path: &[&str]) -> Option<&CommandNode> {
+        path.iter().try_fold(self, |node, name| node.find(name))
+    }
+
+    pub fn usage(&self, program: &str, path: &[&str]) -> String {
+        let mut usage = std::iter::once(program)
+            .chain(path.iter().copied())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        if !self.subcommands.is_empty() {
+            usage.push_str(" <command>");
+        }
+        for arg in self.args {
+            if arg.required {
+                usage.push_str(&format!(" <{}>", arg.name));
+            } else {
+                usage.push_str(&format!(" [{}]", arg.name));
+            }
+        }
+        if !self.flags.is_empty() {
+            usage.push_str(" [options]");
+        }
+
+        usage
+    }
+
+    pub fn render_help(&self, locale: Locale, program: &str, path: &[&str]) -> String {
+        let usage = self.usage(program, path);
+        let mut help = format!(
+            "{}\n\n{}\n",
+            tr(locale, "help.usage", &[("usage", &usage)]),
+            tr(locale, self.help, &[])
+        );
+
+        let commands = self
+            .subcommands
+            .iter()
+            .map(|node| (node.name.to_string(), node.help))
+            .collect::<Vec<_>>();
+        let args = self
+            .args
+            .iter()
+            .map(|arg| (format!("<{}>", arg.name), arg.help))
+            .collect::<Vec<_>>();
+        let options = self
+            .flags
+            .iter()
+            .map(|flag| (format!("--{}", flag.long), flag.help))
+            .chain(std::iter::once((HELP_FLAGS.join(", "), "help.flag.help")))
+            .collect::<Vec<_>>();
+
+        for (title, entries) in [
+            ("help.commands", commands),
+            ("help.arguments", args),
+            ("help.options", options),
+        ] {
+            if entries.is_empty() {
+                continue;
+            }
+
+            let width = entries
+                .iter()
+                .map(|(name, _)| name.len())
+                .max()
+                .unwrap_or(0);
+            help.push_str(&format!("\n{}:\n", tr(locale, title, &[])));
+            for (name, key) in entries {
+                help.push_str(&format!("  {name:<width$}   {}\n", tr(locale, key, &[])));
+            }
+        }
+
+        if !self.subcommands.is_empty() {
+            let command = std::iter::once(program)
+                .chain(path.iter().copied())
+                .chain(std::iter::once("<command>"))
+                .collect::<Vec<_>>()
+                .join(" ");
+            help.push_str(&format!(
+                "\n{}\n",
+                tr(locale, "help.more", &[("command", &command)])
+            ));
+        }
+
+        help
+    }
+
+    fn parse_flag(&self, token: &str, path: &[&'static str]) -> Result<&'static str, ParseError> {
+        self.flags
+            .iter()
+            .find(|flag| token.strip_prefix("--") == Some(flag.long))
+            .map(|flag| flag.long)
+            .ok_or_else(|| ParseError::UnknownFlag {
+                path: path.to_vec(),
+                token: token.to_string(),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TREE: CommandNode = CommandNode {
+        name: "test",
+        help: "",
+        args: &[],
+        flags: &[],
+        subcommands: &[
+            CommandNode {
+                name: "echo",
+                help: "",
+                args: &[
+                    ArgSpec {
+                        name: "word",
+                        help: "",
+                        required: true,
+                        validate: None,
+                    },
+                    ArgSpec {
+                        name: "number",
+                        help: "",
+                        required: false,
+                        validate: Some(|token| token.parse::<u32>().is_ok()),
+                    },
+                ],
+                flags: &[FlagSpec {
+                    long: "loud",
+                    help: "",
+                }],
+                subcommands: &[],
+            },
+            CommandNode {
+                name: "disk",
+                help: "",
+                args: &[],
+                flags: &[],
+                subcommands: &[CommandNode {
+                    name: "wipe",
+                    help: "",
+                    args: &[],
+                    flags: &[],
+                    subcommands: &[],
+                }],
+            },
+        ],
+    };
+
+    #[test]
+    fn parse_nested_subcommand() {
+        let parsed = TREE.parse("disk wipe").unwrap();
+
+        assert_eq!(parsed.path, vec!["disk", "wipe"]);
+        assert!(parsed.args.is_empty());
+    }
+
+    #[test]
+    fn parse_args_and_flags() {
+        let parsed = TREE.parse("echo hello --loud 3").unwrap();
+
+        assert_eq!(parsed.path, vec!["echo"]);
+        assert_eq!(parsed.arg("word"), Some("hello"));
+        assert_eq!(parsed.arg("number"), Some("3"));
+        assert!(parsed.has_flag("loud"));
+    }
+
+    #[test]
+    fn parse_unknown_subcommand_names_token() {
+        assert_eq!(
+            TREE.parse("disk format"),
+            Err(ParseError::UnknownCommand {
+                path: vec!["disk"],
+                token: "format".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_missing_subcommand() {
+        assert_eq!(
+            TREE.parse("disk"),
+            Err(ParseError::MissingCommand { path: vec!["disk"] })
+        );
+        assert_eq!(
+            TREE.parse(""),
+            Err(ParseError::MissingCommand { path: vec![] })
+        );
+    }
+
+    #[test]
+    fn parse_help_requests() {
+        let parsed = TREE.parse("help").unwrap();
+        assert!(parsed.help);
+        assert!(parsed.path.is_empty());
+
+        let parsed = TREE.parse("disk help").unwrap();
+        assert!(parsed.help);
+        assert_eq!(parsed.path, vec!["disk"]);
+
+        let parsed = TREE.parse("echo --help").unwrap();
+        assert!(parsed.help);
+        assert_eq!(parsed.path, vec!["echo"]);
+    }
+
+    #[test]
+    fn render_help_for_node() {
+        let node = TREE.resolve(&["echo"]).unwrap();
+        let help = node.render_help(Locale::En, "@bot", &["echo"]);
+
+        assert!(help.starts_with("Usage: @bot echo <word> [number] [options]"));
+        assert!(help.contains("--loud"));
+        assert!(help.contains("<number>"));
+
+        let help = TREE.render_help(Locale::En, "@bot", &[]);
+        assert!(help.contains("echo"));
+        assert!(help.contains("disk"));
+        assert!(!help.contains("wipe"));
+    }
+
+    #[test]
+    fn parse_argument_errors() {
+        assert_eq!(
+            TREE.parse("echo"),
+            Err(ParseError::MissingArgument {
+                path: vec!["echo"],
+                arg: "word",
+            })
+        );
+        assert_eq!(
+            TREE.parse("echo hello many"),
+            Err(ParseError::InvalidArgument {
+                path: vec!["echo"],
+                arg: "number",
+                token: "many".to_string(),
+            })
+        );
+        assert_eq!(
+            TREE.parse("echo hello 1 2"),
+            Err(ParseError::UnexpectedArgument {
+                path: vec!["echo"],
+                token: "2".to_string(),
+            })
+        );
+        assert_eq!(
+            TREE.parse("disk wipe --quiet"),
+            Err(ParseError::UnknownFlag {
+                path: vec!["disk", "wipe"],
+                token: "--quiet".to_string(),
+            })
+        );
+    }
+}
@@ -1,4 +1,3 @@
-use ft_api::config_env_var;
 use slack_morphism::prelude::*;
 
 use bytes::Bytes;
@@ -6,13 +5,23 @@ use http_body_util::combinators::BoxBody;
 use http_body_util::{BodyExt, Empty, Full};
 use hyper::Response;
 
+use axum::extract::Query;
 use axum::Extension;
+use serde::Deserialize;
 use std::convert::Infallible;
 use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::mpsc;
-use tracing::debug;
+use tracing::{debug, error};
 
 use crate::bot_cmd::*;
+use crate::config::Config;
+use crate::confirm::{ConfirmError, PendingConfirmations, CANCEL_ACTION, CONFIRM_ACTION};
+use crate::dedup::SeenEvents;
+use crate::i18n::{tr, Locale};
+use crate::link::AccountLinker;
+use crate::reply;
+use crate::user::{LinkSource, UserStore};
 
 pub async fn oauth_install_function(
     resp: SlackOAuthV2AccessTokenResponse,
@@ -22,20 +31,76 @@ pub async fn oauth_install_function(
     println!("{:#?}", resp);
 }
 
-pub async fn push_event(
-    Extension(_environment): Extension<Arc<SlackHyperListenerEnvironment>>,
-    Extension(event): Extension<SlackPushEvent>,
-    Extension(sender): Extension<mpsc::Sender<BotTask>>,
-) -> Response<BoxBody<Bytes, Infallible>> {
-    match event {
-        SlackPushEvent::UrlVerification(url_ver) => {
-            Response::new(Full::new(url_ver.challenge.into()).boxed())
+// Everything the event handlers need to turn Slack events into tasks,
+// whichever transport (HTTP or Socket Mode) delivered them.
+#[derive(Clone)]
+pub struct Pipeline {
+    pub client: Arc<SlackHyperClient>,
+    pub config: Arc<Config>,
+    pub sender: mpsc::Sender<BotTask>,
+    pub confirmations: Arc<PendingConfirmations>,
+    pub seen: Arc<SeenEvents>,
+}
+
+// Looks up who sent the message so commands can check staff rights and
+// answer in the user's language.
+pub async fn message_context(
+    session: &SlackClientSession<'_, SlackClientHyperHttpsConnector>,
+    request: SlackRequest,
+    default_locale: Locale,
+) -> Option<SlackMessageContext> {
+    let SlackRequest {
+        user,
+        channel,
+        source,
+        text,
+    } = request;
+    let user_info = session
+        .users_info(&SlackApiUsersInfoRequest::new(user.clone()).with_include_locale(true))
+        .await;
+    let user_info = match user_info {
+        Ok(user_info) => user_info,
+        Err(err) => {
+            error!("Could not look up Slack user {user}: {err}");
+            return None;
         }
-        SlackPushEvent::EventCallback(callback) => {
-            let token = SlackApiToken::new(config_env_var("SLACK_TOKEN").unwrap().into());
-            let session = _environment.client.open_session(&token);
+    };
 
-            if let SlackEventCallbackBody::AppMention(SlackAppMentionEvent {
+    // Only the lookup itself is required. A missing name is just not logged
+    // and a missing admin flag means no staff rights.
+    let SlackUser {
+        real_name,
+        locale,
+        flags: SlackUserFlags { is_admin, .. },
+        ..
+    } = user_info.user;
+    let real_name = real_name.unwrap_or_default();
+    let is_admin = is_admin.unwrap_or(false);
+    let locale = locale
+        .and_then(|locale| Locale::parse(&locale.0))
+        .unwrap_or(default_locale);
+    debug!("message from user:{real_name}, is_admin:{is_admin}, locale:{locale:?}, text:{text}");
+    Some(SlackMessageContext {
+        channel,
+        source,
+        user,
+        real_name,
+        is_admin,
+        locale,
+        text,
+    })
+}
+
+impl Pipeline {
+    async fn submit(&self, request: SlackRequest) {
+        let _ = self.sender.send(BotTask::Request(request)).await;
+    }
+
+    // Only queues the event, so Slack gets its ack before it thinks about
+    // retrying. Retries that still come through are dropped here.
+    pub async fn on_push(&self, callback: SlackPushEventCallback) {
+        let request = match callback.event {
+            SlackEventCallbackBody::AppMention(SlackAppMentionEvent {
                 user,
                 channel,
                 content:
@@ -44,46 +109,279 @@ pub async fn push_event(
                     },
                 origin: SlackMessageOrigin { ts, thread_ts, .. },
                 ..
-            }) = callback.event
-            {
-                let user_info = session
-                    .users_info(&SlackApiUsersInfoRequest::new(user))
-                    .await;
+            }) => Some(SlackRequest {
+                user,
+                channel,
+                source: MessageSource::Mention { ts, thread_ts },
+                text,
+            }),
+            SlackEventCallbackBody::Message(event) => direct_message(event),
+            _ => None,
+        };
+        let Some(request) = request else {
+            return;
+        };
+
+        let mut keys = vec![callback.event_id.0];
+        if let MessageSource::Mention { ts, .. } | MessageSource::DirectMessage { ts, .. } =
+            &request.source
+        {
+            keys.push(format!("{}:{ts}", request.channel));
+        }
+        if self.seen.first_time(&keys, Instant::now()) {
+            self.submit(request).await;
+        } else {
+            debug!("Dropped duplicate delivery of {}", keys[0]);
+        }
+    }
+
+    // `/gsctl ...` goes through the same task queue as mentions. Slack only
+    // waits three seconds for the ack, so the result is sent to `response_url`.
+    pub async fn on_command(&self, event: SlackCommandEvent) {
+        let source = MessageSource::SlashCommand {
+            response_url: event.response_url,
+        };
+        self.submit(SlackRequest {
+            user: event.user_id,
+            channel: event.channel_id,
+            source,
+            text: event.text.unwrap_or_default(),
+        })
+        .await;
+    }
+
+    // Confirm/Cancel clicks on the prompt posted for destructive commands.
+    // Answering the click is a Slack call of its own, so it runs after the ack.
+    pub async fn on_interaction(&self, event: SlackInteractionEvent) {
+        let SlackInteractionEvent::BlockActions(SlackInteractionBlockActionsEvent {
+            user: Some(user),
+            actions: Some(actions),
+            response_url: Some(response_url),
+            ..
+        }) = event
+        else {
+            return;
+        };
+        let Some((confirmed, id)) = actions.into_iter().find_map(|action| {
+            let confirmed = match action.action_id.0.as_str() {
+                CONFIRM_ACTION => true,
+                CANCEL_ACTION => false,
+                _ => return None,
+            };
+            Some((confirmed, action.value?))
+        }) else {
+            return;
+        };
 
-                if let Ok(SlackApiUsersInfoResponse {
-                    user:
-                        SlackUser {
-                            real_name: Some(real_name),
-                            flags:
-                                SlackUserFlags {
-                                    is_admin: Some(is_admin),
-                                    ..
-                                },
-                            ..
-                        },
-                }) = user_info
-                {
-                    debug!("message from user:{real_name}, is_admin:{is_admin}, text:{text}");
-                    let bot_cmd = BotTask {
-                        message_context: SlackMessageContext {
-                            channel,
-                            ts,
-                            thread_ts,
-                            real_name,
-                            is_admin,
-                            text,
-                        },
-                    };
-                    let _ = sender.send(bot_cmd).await;
+        let pipeline = self.clone();
+        tokio::spawn(async move {
+            pipeline
+                .answer_click(user.id, confirmed, id, response_url)
+                .await
+        });
+    }
+
+    async fn answer_click(
+        &self,
+        user: SlackUserId,
+        confirmed: bool,
+        id: String,
+        response_url: SlackResponseUrl,
+    ) {
+        let client = &self.client;
+        let slack = &self.config.slack;
+
+        match self.confirmations.take(&id, &user, Instant::now()) {
+            Ok((message_context, command)) => {
+                let locale = message_context.locale;
+                let text = message_context
+                    .command_tokens(&slack.wakeup_word)
+                    .map(|tokens| tokens.collect::<Vec<_>>().join(" "))
+                    .unwrap_or_default();
+                if confirmed {
+                    debug!("{user} confirmed `{text}`");
+                    reply::answer_click(
+                        client,
+                        &response_url,
+                        tr(locale, "confirm.accepted", &[("command", &text)]),
+                        true,
+                    )
+                    .await;
+                    let _ = self
+                        .sender
+                        .send(BotTask::Confirmed {
+                            message_context,
+                            command,
+                        })
+                        .await;
+                } else {
+                    debug!("{user} cancelled `{text}`");
+                    reply::answer_click(
+                        client,
+                        &response_url,
+                        tr(locale, "confirm.cancelled", &[]),
+                        true,
+                    )
+                    .await;
                 }
             }
+            Err(ConfirmError::NotRequester) => {
+                reply::answer_click(
+                    client,
+                    &response_url,
+                    tr(slack.default_locale, "confirm.not_requester", &[]),
+                    false,
+                )
+                .await;
+            }
+            Err(err) => {
+                debug!("Rejected click by {user}: {err}");
+                reply::answer_click(
+                    client,
+                    &response_url,
+                    tr(slack.default_locale, "confirm.expired", &[]),
+                    true,
+                )
+                .await;
+            }
+        }
+    }
+}
+
+// A plain message a student sent to the bot's DM. Edits, deletions and
+// anything posted by a bot (our own replies included) carry a subtype or a
+// bot id and are ignored.
+fn direct_message(event: SlackMessageEvent) -> Option<SlackRequest> {
+    let SlackMessageEvent {
+        origin:
+            SlackMessageOrigin {
+                ts,
+                channel: Some(channel),
+                channel_type: Some(channel_type),
+                thread_ts,
+                ..
+            },
+        content: Some(SlackMessageContent {
+            text: Some(text), ..
+        }),
+        sender:
+            SlackMessageSender {
+                user: Some(user),
+                bot_id: None,
+                ..
+            },
+        subtype: None,
+        ..
+    } = event
+    else {
+        return None;
+    };
 
+    (channel_type.0 == "im").then_some(SlackRequest {
+        user,
+        channel,
+        source: MessageSource::DirectMessage { ts, thread_ts },
+        text,
+    })
+}
+
+pub async fn push_event(
+    Extension(pipeline): Extension<Pipeline>,
+    Extension(event): Extension<SlackPushEvent>,
+) -> Response<BoxBody<Bytes, Infallible>> {
+    match event {
+        SlackPushEvent::UrlVerification(url_ver) => {
+            Response::new(Full::new(url_ver.challenge.into()).boxed())
+        }
+        SlackPushEvent::EventCallback(callback) => {
+            pipeline.on_push(callback).await;
             Response::new(Empty::new().boxed())
         }
         _ => Response::new(Empty::new().boxed()),
     }
 }
 
+pub async fn command_event(
+    Extension(pipeline): Extension<Pipeline>,
+    Extension(event): Extension<SlackCommandEvent>,
+) -> Response<BoxBody<Bytes, Infallible>> {
+    pipeline.on_command(event).await;
+    Response::new(Empty::new().boxed())
+}
+
+pub async fn interaction_event(
+    Extension(pipeline): Extension<Pipeline>,
+    Extension(event): Extension<SlackInteractionEvent>,
+) -> Response<BoxBody<Bytes, Infallible>> {
+    pipeline.on_interaction(event).await;
+    Response::new(Empty::new().boxed())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LinkCallback {
+    code: Option<String>,
+    state: Option<String>,
+}
+
+pub async fn link_callback(
+    Extension(config): Extension<Arc<Config>>,
+    Extension(linker): Extension<Arc<AccountLinker>>,
+    Extension(users): Extension<Arc<UserStore>>,
+    Query(callback): Query<LinkCallback>,
+) -> Response<BoxBody<Bytes, Infallible>> {
+    let text_response = |status: HttpStatusCode, text: String| {
+        let mut response = Response::new(Full::new(text.into()).boxed());
+        *response.status_mut() = status;
+        response
+    };
+
+    let pending = match linker.pending.take(
+        callback.state.as_deref().unwrap_or_default(),
+        Instant::now(),
+    ) {
+        Ok(pending) => pending,
+        Err(err) => {
+            debug!("Rejected link callback: {err}");
+            return text_response(
+                HttpStatusCode::BAD_REQUEST,
+                tr(config.slack.default_locale, "link.expired", &[]),
+            );
+        }
+    };
+    let locale = pending.locale;
+
+    // 42 intra redirects without a code when the student cancels.
+    let Some(code) = callback.code else {
+        return text_response(HttpStatusCode::BAD_REQUEST, tr(locale, "link.denied", &[]));
+    };
+
+    let bound = match linker.verify(&code).await {
+        Ok(login) => users
+            .bind(
+                pending.slack_id.clone(),
+                login.clone(),
+                LinkSource::Verified,
+            )
+            .map(|_| login)
+            .map_err(|err| err.to_string()),
+        Err(err) => Err(err.to_string()),
+    };
+
+    match bound {
+        Ok(login) => {
+            debug!("Linked {} to {login}", pending.slack_id);
+            text_response(
+                HttpStatusCode::OK,
+                tr(locale, "link.done", &[("login", &login.0)]),
+            )
+        }
+        Err(err) => {
+            error!("Could not link {}: {err}", pending.slack_id);
+            text_response(HttpStatusCode::BAD_GATEWAY, tr(locale, "link.failed", &[]))
+        }
+    }
+}
+
 pub fn error_handler(
     err: Box<dyn std::error::Error + Send + Sync>,
     _client: Arc<SlackHyperClient>,
@@ -94,3 +392,45 @@ pub fn error_handler(
     // Defines what we return Slack server
     HttpStatusCode::BAD_REQUEST
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message_event(extra: serde_json::Value) -> SlackMessageEvent {
+        let mut event = serde_json::json!({
+            "ts": "1700000000.000100",
+            "channel": "D1",
+            "channel_type": "im",
+            "user": "U1",
+            "text": "home reset",
+        });
+        event
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(event).unwrap()
+    }
+
+    #[test]
+    fn only_user_messages_in_dms_are_commands() {
+        let request = direct_message(message_event(serde_json::json!({}))).unwrap();
+        assert_eq!(request.user.0, "U1");
+        assert_eq!(request.channel.0, "D1");
+        assert!(matches!(
+            request.source,
+            MessageSource::DirectMessage { .. }
+        ));
+        assert_eq!(request.text, "home reset");
+
+        assert!(direct_message(message_event(
+            serde_json::json!({ "channel_type": "channel" })
+        ))
+        .is_none());
+        assert!(direct_message(message_event(serde_json::json!({ "bot_id": "B1" }))).is_none());
+        assert!(direct_message(message_event(
+            serde_json::json!({ "subtype": "message_changed" })
+        ))
+        .is_none());
+    }
+}
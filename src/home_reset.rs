@@ -0,0 +1,280 @@
+use ft_api::FtLoginId;
+use std::fmt;
+use tracing::debug;
+
+use crate::homemaker::{HomeStatus, HomemakerError, HomesApi};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetStep {
+    Snapshot,
+    Delete,
+    Create,
+    Verify,
+}
+
+impl fmt::Display for ResetStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResetStep::Snapshot => write!(f, "snapshot"),
+            ResetStep::Delete => write!(f, "delete"),
+            ResetStep::Create => write!(f, "create"),
+            ResetStep::Verify => write!(f, "verify"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ResetOutcome {
+    Done {
+        completed: Vec<ResetStep>,
+    },
+    Aborted {
+        failed: ResetStep,
+        error: HomemakerError,
+    },
+    Partial {
+        completed: Vec<ResetStep>,
+        failed: ResetStep,
+        error: HomemakerError,
+        snapshot: Option<HomeStatus>,
+    },
+    // The home was created again but its state could not be read back.
+    Unverified {
+        completed: Vec<ResetStep>,
+        error: HomemakerError,
+    },
+}
+
+const CREATE_ATTEMPTS: usize = 2;
+
+pub async fn reset_home(homes: &dyn HomesApi, login: &FtLoginId) -> ResetOutcome {
+    let mut completed = vec![];
+
+    let snapshot = match homes.status(login).await {
+        Ok(status) => Some(status),
+        Err(HomemakerError::NotFound(_)) => None,
+        Err(error) => {
+            return ResetOutcome::Aborted {
+                failed: ResetStep::Snapshot,
+                error,
+            }
+        }
+    };
+    debug!("Home reset of {login}: snapshot {snapshot:?}");
+    completed.push(ResetStep::Snapshot);
+
+    if snapshot.is_some() {
+        if let Err(error) = homes.delete(login).await {
+            // A failed or timed out delete may still have gone through on the
+            // storage side, so look before deciding nothing changed.
+            match homes.status(login).await {
+                Err(HomemakerError::NotFound(_)) => {
+                    debug!("Home reset of {login}: delete reported {error} but home is gone")
+                }
+                _ => {
+                    return ResetOutcome::Aborted {
+                        failed: ResetStep::Delete,
+                        error,
+                    }
+                }
+            }
+        }
+        completed.push(ResetStep::Delete);
+    }
+
+    let mut created = homes.create(login).await;
+    // Whether an attempt may have gone through without us hearing back.
+    let mut maybe_created = false;
+    for _ in 1..CREATE_ATTEMPTS {
+        match created {
+            Err(HomemakerError::Conflict(_)) | Ok(()) => break,
+            Err(ref error) => {
+                debug!("Home reset of {login}: create failed with {error}, retrying");
+                maybe_created |= matches!(
+                    error,
+                    HomemakerError::Timeout | HomemakerError::Transport(_)
+                );
+                created = homes.create(login).await;
+            }
+        }
+    }
+    // A conflict after a create that timed out means that create went through
+    // after all. Any other conflict means the old home is still there.
+    if let Err(HomemakerError::Conflict(body)) = &created {
+        if maybe_created {
+            debug!("Home reset of {login}: create found the home already there: {body}");
+            created = Ok(());
+        }
+    }
+    if let Err(error) = created {
+        return ResetOutcome::Partial {
+            completed,
+            failed: ResetStep::Create,
+            error,
+            snapshot,
+        };
+    }
+    completed.push(ResetStep::Create);
+
+    if let Err(error) = homes.status(login).await {
+        return ResetOutcome::Unverified { completed, error };
+    }
+    completed.push(ResetStep::Verify);
+
+    ResetOutcome::Done { completed }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::homemaker::ScriptedHomes;
+
+    fn status() -> HomeStatus {
+        HomeStatus {
+            state: "open".to_string(),
+            initiator: None,
+        }
+    }
+
+    fn login() -> FtLoginId {
+        FtLoginId("student".to_string())
+    }
+
+    #[tokio::test]
+    async fn reset_runs_every_step_in_order() {
+        let homes = ScriptedHomes::new();
+        homes
+            .push_status(status())
+            .push_ok()
+            .push_ok()
+            .push_status(status());
+
+        let outcome = reset_home(&homes, &login()).await;
+
+        assert!(matches!(outcome, ResetOutcome::Done { completed } if completed.len() == 4));
+        assert_eq!(
+            homes.calls(),
+            vec![
+                "status student",
+                "delete student",
+                "create student",
+                "status student"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_delete_skips_create() {
+        let homes = ScriptedHomes::new();
+        homes
+            .push_status(status())
+            .push_err(HomemakerError::Unauthorized)
+            .push_status(status());
+
+        let outcome = reset_home(&homes, &login()).await;
+
+        assert!(matches!(
+            outcome,
+            ResetOutcome::Aborted {
+                failed: ResetStep::Delete,
+                ..
+            }
+        ));
+        assert!(!homes.calls().contains(&"create student".to_string()));
+    }
+
+    #[tokio::test]
+    async fn failed_create_is_a_partial_failure() {
+        let homes = ScriptedHomes::new();
+        homes
+            .push_status(status())
+            .push_ok()
+            .push_err(HomemakerError::Timeout)
+            .push_err(HomemakerError::Timeout);
+
+        let outcome = reset_home(&homes, &login()).await;
+
+        assert!(matches!(
+            outcome,
+            ResetOutcome::Partial {
+                failed: ResetStep::Create,
+                ref completed,
+                ..
+            } if completed == &vec![ResetStep::Snapshot, ResetStep::Delete]
+        ));
+    }
+
+    #[tokio::test]
+    async fn conflict_after_a_timed_out_create_means_created() {
+        let homes = ScriptedHomes::new();
+        homes
+            .push_status(status())
+            .push_ok()
+            .push_err(HomemakerError::Timeout)
+            .push_err(HomemakerError::Conflict("home exists".to_string()))
+            .push_status(status());
+
+        let outcome = reset_home(&homes, &login()).await;
+
+        assert!(matches!(outcome, ResetOutcome::Done { completed } if completed.len() == 4));
+        assert_eq!(homes.calls().last().unwrap(), "status student");
+    }
+
+    #[tokio::test]
+    async fn conflict_on_the_first_create_is_a_partial_failure() {
+        let homes = ScriptedHomes::new();
+        homes
+            .push_status(status())
+            .push_ok()
+            .push_err(HomemakerError::Conflict("home exists".to_string()));
+
+        let outcome = reset_home(&homes, &login()).await;
+
+        assert!(matches!(
+            outcome,
+            ResetOutcome::Partial {
+                failed: ResetStep::Create,
+                error: HomemakerError::Conflict(_),
+                ..
+            }
+        ));
+        assert_eq!(
+            homes.calls(),
+            vec!["status student", "delete student", "create student"]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_verify_is_not_a_partial_failure() {
+        let homes = ScriptedHomes::new();
+        homes
+            .push_status(status())
+            .push_ok()
+            .push_ok()
+            .push_err(HomemakerError::Timeout);
+
+        let outcome = reset_home(&homes, &login()).await;
+
+        assert!(matches!(
+            outcome,
+            ResetOutcome::Unverified { ref completed, .. } if completed.len() == 3
+        ));
+    }
+
+    #[tokio::test]
+    async fn missing_home_is_only_created() {
+        let homes = ScriptedHomes::new();
+        homes
+            .push_err(HomemakerError::NotFound(login()))
+            .push_ok()
+            .push_status(status());
+
+        let outcome = reset_home(&homes, &login()).await;
+
+        assert!(matches!(outcome, ResetOutcome::Done { .. }));
+        assert_eq!(
+            homes.calls(),
+            vec!["status student", "create student", "status student"]
+        );
+    }
+}
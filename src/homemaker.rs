@@ -0,0 +1,308 @@
+use ft_api::FtLoginId;
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
+use serde::{Deserialize, Serialize};
+#[cfg(test)]
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+#[cfg(test)]
+use std::sync::Mutex;
+use url::Url;
+
+use crate::timeouts::Timeouts;
+
+#[derive(Debug)]
+pub enum HomemakerError {
+    Config(String),
+    Unauthorized,
+    NotFound(FtLoginId),
+    Conflict(String),
+    Api { status: StatusCode, body: String },
+    Timeout,
+    Transport(reqwest::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HomeAction {
+    Create,
+    Delete,
+    Close,
+    Status,
+}
+
+impl fmt::Display for HomemakerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HomemakerError::Config(msg) => write!(f, "homemaker config error: {msg}"),
+            HomemakerError::Unauthorized => write!(f, "homemaker rejected the api token"),
+            HomemakerError::NotFound(login) => write!(f, "no home for {login}"),
+            HomemakerError::Conflict(body) => write!(f, "homemaker conflict: {body}"),
+            HomemakerError::Api { status, body } => write!(f, "homemaker error {status}: {body}"),
+            HomemakerError::Timeout => write!(f, "homemaker did not answer in time"),
+            HomemakerError::Transport(err) => write!(f, "homemaker unreachable: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for HomemakerError {}
+
+impl From<reqwest::Error> for HomemakerError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            HomemakerError::Timeout
+        } else {
+            HomemakerError::Transport(err)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct HomeStatus {
+    pub state: String,
+    pub initiator: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct CloseRequest<'a> {
+    initiator: &'a str,
+    force: bool,
+}
+
+#[derive(Clone)]
+pub struct HomemakerClient {
+    http: Client,
+    url: Url,
+    token: String,
+    timeouts: Timeouts,
+}
+
+impl fmt::Debug for HomemakerClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HomemakerClient")
+            .field("url", &self.url.as_str())
+            .field("token", &"<redacted>")
+            .finish()
+    }
+}
+
+impl HomemakerClient {
+    pub fn new(url: &str, token: String, timeouts: Timeouts) -> Result<Self, HomemakerError> {
+        let url = Url::parse(url).map_err(|err| HomemakerError::Config(format!("{url}: {err}")))?;
+        if url.cannot_be_a_base() {
+            return Err(HomemakerError::Config(format!("{url} is not a base url")));
+        }
+
+        Ok(HomemakerClient {
+            http: Client::new(),
+            url,
+            token,
+            timeouts,
+        })
+    }
+
+    fn home_url(&self, login: &FtLoginId, action: Option<&str>) -> Url {
+        let mut url = self.url.clone();
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.pop_if_empty().push("homes").push(&login.0);
+            if let Some(action) = action {
+                segments.push(action);
+            }
+        }
+        url
+    }
+
+    fn request(&self, action: HomeAction, method: Method, url: Url) -> RequestBuilder {
+        self.http
+            .request(method, url)
+            .bearer_auth(&self.token)
+            .timeout(self.timeouts.home(action))
+    }
+
+    async fn send(
+        &self,
+        request: RequestBuilder,
+        login: &FtLoginId,
+    ) -> Result<Response, HomemakerError> {
+        let response = request.send().await?;
+
+        match response.status() {
+            status if status.is_success() => Ok(response),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(HomemakerError::Unauthorized),
+            StatusCode::NOT_FOUND => Err(HomemakerError::NotFound(login.clone())),
+            StatusCode::CONFLICT => Err(HomemakerError::Conflict(
+                response.text().await.unwrap_or_default(),
+            )),
+            status => Err(HomemakerError::Api {
+                status,
+                body: response.text().await.unwrap_or_default(),
+            }),
+        }
+    }
+
+    pub async fn create(&self, login: &FtLoginId) -> Result<(), HomemakerError> {
+        let request = self.request(HomeAction::Create, Method::POST, self.home_url(login, None));
+        self.send(request, login).await.map(|_| ())
+    }
+
+    pub async fn delete(&self, login: &FtLoginId) -> Result<(), HomemakerError> {
+        let request = self.request(
+            HomeAction::Delete,
+            Method::DELETE,
+            self.home_url(login, None),
+        );
+        self.send(request, login).await.map(|_| ())
+    }
+
+    pub async fn close(&self, login: &FtLoginId, initiator: &str) -> Result<(), HomemakerError> {
+        let request = self
+            .request(
+                HomeAction::Close,
+                Method::POST,
+                self.home_url(login, Some("close")),
+            )
+            .json(&CloseRequest {
+                initiator,
+                force: true,
+            });
+        self.send(request, login).await.map(|_| ())
+    }
+
+    pub async fn status(&self, login: &FtLoginId) -> Result<HomeStatus, HomemakerError> {
+        let request = self.request(HomeAction::Status, Method::GET, self.home_url(login, None));
+        Ok(self.send(request, login).await?.json().await?)
+    }
+}
+
+pub type HomesFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, HomemakerError>> + Send + 'a>>;
+
+pub trait HomesApi: Send + Sync {
+    fn create<'a>(&'a self, login: &'a FtLoginId) -> HomesFuture<'a, ()>;
+    fn delete<'a>(&'a self, login: &'a FtLoginId) -> HomesFuture<'a, ()>;
+    fn close<'a>(&'a self, login: &'a FtLoginId, initiator: &'a str) -> HomesFuture<'a, ()>;
+    fn status<'a>(&'a self, login: &'a FtLoginId) -> HomesFuture<'a, HomeStatus>;
+}
+
+impl HomesApi for HomemakerClient {
+    fn create<'a>(&'a self, login: &'a FtLoginId) -> HomesFuture<'a, ()> {
+        Box::pin(HomemakerClient::create(self, login))
+    }
+
+    fn delete<'a>(&'a self, login: &'a FtLoginId) -> HomesFuture<'a, ()> {
+        Box::pin(HomemakerClient::delete(self, login))
+    }
+
+    fn close<'a>(&'a self, login: &'a FtLoginId, initiator: &'a str) -> HomesFuture<'a, ()> {
+        Box::pin(HomemakerClient::close(self, login, initiator))
+    }
+
+    fn status<'a>(&'a self, login: &'a FtLoginId) -> HomesFuture<'a, HomeStatus> {
+        Box::pin(HomemakerClient::status(self, login))
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct ScriptedHomes {
+    script: Mutex<VecDeque<Result<Option<HomeStatus>, HomemakerError>>>,
+    calls: Mutex<Vec<String>>,
+}
+
+#[cfg(test)]
+impl ScriptedHomes {
+    pub fn new() -> Self {
+        ScriptedHomes::default()
+    }
+
+    pub fn push_ok(&self) -> &Self {
+        self.script.lock().unwrap().push_back(Ok(None));
+        self
+    }
+
+    pub fn push_status(&self, status: HomeStatus) -> &Self {
+        self.script.lock().unwrap().push_back(Ok(Some(status)));
+        self
+    }
+
+    pub fn push_err(&self, err: HomemakerError) -> &Self {
+        self.script.lock().unwrap().push_back(Err(err));
+        self
+    }
+
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    fn next(&self, call: String) -> Result<Option<HomeStatus>, HomemakerError> {
+        self.calls.lock().unwrap().push(call.clone());
+        self.script.lock().unwrap().pop_front().unwrap_or_else(|| {
+            Err(HomemakerError::Config(format!(
+                "no scripted result for {call}"
+            )))
+        })
+    }
+
+    fn next_unit<'a>(&self, call: String) -> HomesFuture<'a, ()> {
+        let result = self.next(call).map(|_| ());
+        Box::pin(async move { result })
+    }
+}
+
+#[cfg(test)]
+impl HomesApi for ScriptedHomes {
+    fn create<'a>(&'a self, login: &'a FtLoginId) -> HomesFuture<'a, ()> {
+        self.next_unit(format!("create {login}"))
+    }
+
+    fn delete<'a>(&'a self, login: &'a FtLoginId) -> HomesFuture<'a, ()> {
+        self.next_unit(format!("delete {login}"))
+    }
+
+    fn close<'a>(&'a self, login: &'a FtLoginId, initiator: &'a str) -> HomesFuture<'a, ()> {
+        self.next_unit(format!("close {login} {initiator}"))
+    }
+
+    fn status<'a>(&'a self, login: &'a FtLoginId) -> HomesFuture<'a, HomeStatus> {
+        let result = self
+            .next(format!("status {login}"))
+            .and_then(|status| status.ok_or_else(|| HomemakerError::NotFound(login.clone())));
+        Box::pin(async move { result })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn home_url_escapes_login() {
+        let client = HomemakerClient::new(
+            "http://storage:8080/api/",
+            "token".into(),
+            Timeouts::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            client
+                .home_url(&FtLoginId("student".to_string()), Some("close"))
+                .as_str(),
+            "http://storage:8080/api/homes/student/close"
+        );
+        assert_eq!(
+            client
+                .home_url(&FtLoginId("../admin?x=1".to_string()), None)
+                .as_str(),
+            "http://storage:8080/api/homes/..%2Fadmin%3Fx=1"
+        );
+    }
+
+    #[test]
+    fn debug_hides_token() {
+        let client =
+            HomemakerClient::new("http://storage", "t0ps3cret".into(), Timeouts::default())
+                .unwrap();
+
+        assert!(!format!("{client:?}").contains("t0ps3cret"));
+    }
+}
@@ -0,0 +1,469 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Locale {
+    #[default]
+    Ko,
+    En,
+}
+
+impl Locale {
+    pub fn parse(tag: &str) -> Option<Self> {
+        match tag.split(['-', '_']).next()?.to_ascii_lowercase().as_str() {
+            "ko" => Some(Locale::Ko),
+            "en" => Some(Locale::En),
+            _ => None,
+        }
+    }
+
+    fn catalog(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Locale::Ko => KO,
+            Locale::En => EN,
+        }
+    }
+
+    fn lookup(self, key: &str) -> Option<&'static str> {
+        self.catalog()
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, template)| *template)
+    }
+}
+
+impl FromStr for Locale {
+    type Err = String;
+
+    fn from_str(tag: &str) -> Result<Self, Self::Err> {
+        Locale::parse(tag).ok_or_else(|| "expected `ko` or `en`".to_string())
+    }
+}
+
+pub fn tr(locale: Locale, key: &str, args: &[(&str, &str)]) -> String {
+    let template = locale
+        .lookup(key)
+        .or_else(|| Locale::En.lookup(key))
+        .unwrap_or(key);
+
+    let mut message = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        message.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let value = rest.find('}').and_then(|end| {
+            args.iter()
+                .find(|(name, _)| *name == &rest[1..end])
+                .map(|(_, value)| (end, *value))
+        });
+        match value {
+            Some((end, value)) => {
+                message.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                message.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    message.push_str(rest);
+
+    message
+}
+
+const KO: &[(&str, &str)] = &[
+    ("help.gsctl", "클러스터 PC와 home 디렉토리를 관리합니다."),
+    ("help.reboot", "로그인한 PC를 재부팅합니다."),
+    (
+        "help.reboot.host",
+        "내 PC 대신 재부팅할 호스트입니다. (스태프 전용)",
+    ),
+    ("help.home", "'home' 디렉토리와 관련된 작업을 관리합니다."),
+    ("help.home.reset", "home을 기본 상태로 재설정합니다."),
+    ("help.home.close", "remote home과 pc의 연결을 끊습니다."),
+    ("help.goinfre", "로그인한 PC의 goinfre를 관리합니다."),
+    ("help.goinfre.reset", "goinfre를 비웁니다."),
+    ("help.update", "채널 멤버 목록을 갱신합니다."),
+    ("help.link", "42 인트라 계정을 Slack 계정에 연결합니다."),
+    ("help.flag.help", "이 도움말 메시지를 보여줍니다."),
+    ("help.usage", "사용법: {usage}"),
+    ("help.commands", "명령어"),
+    ("help.arguments", "인자"),
+    ("help.options", "옵션"),
+    (
+        "help.more",
+        "각 명령어의 자세한 사용법은 `{command} --help`로 확인하세요.",
+    ),
+    ("parse.missing_command", "명령어를 입력해 주세요."),
+    (
+        "parse.missing_subcommand",
+        "`{path}` 명령어에는 하위 명령어가 필요합니다.",
+    ),
+    (
+        "parse.unknown_command",
+        "알 수 없는 명령어입니다: `{token}`",
+    ),
+    (
+        "parse.unknown_subcommand",
+        "`{path}`에 없는 하위 명령어입니다: `{token}`",
+    ),
+    (
+        "parse.unknown_flag",
+        "`{path}`에 없는 옵션입니다: `{token}`",
+    ),
+    (
+        "parse.unexpected_argument",
+        "`{path}`에 필요하지 않은 인자입니다: `{token}`",
+    ),
+    (
+        "parse.missing_argument",
+        "`{path}` 명령어에는 <{arg}> 인자가 필요합니다.",
+    ),
+    (
+        "parse.invalid_argument",
+        "`{token}`은(는) `{path}`의 올바른 <{arg}> 값이 아닙니다.",
+    ),
+    (
+        "error.command_failed",
+        "다음 이유로 명령을 실행할 수 없습니다: {reason}",
+    ),
+    (
+        "error.location_not_found",
+        "현재 로그인한 자리를 찾을 수 없습니다.",
+    ),
+    (
+        "error.location_ambiguous",
+        "여러 자리에 로그인되어 있습니다: {hosts}. 사용하지 않는 자리에서 로그아웃해 주세요.",
+    ),
+    (
+        "error.location_unavailable",
+        "42 API에 연결할 수 없어 자리를 확인하지 못했습니다. 잠시 후 다시 시도해 주세요.",
+    ),
+    (
+        "error.sender_unknown",
+        "Slack에서 사용자 정보를 가져오지 못해 요청을 처리할 수 없습니다. 잠시 후 다시 시도해 주세요.",
+    ),
+    (
+        "error.staff_only_reboot",
+        "다른 호스트의 재부팅은 스태프만 할 수 있습니다.",
+    ),
+    (
+        "error.not_registered",
+        "등록된 42 계정이 없습니다. `{command}`로 계정을 연결해 주세요.",
+    ),
+    (
+        "error.not_linked",
+        "이 명령은 42 계정을 인증한 뒤에 사용할 수 있습니다. `{command}`로 계정을 연결해 주세요.",
+    ),
+    (
+        "error.staff_only_update",
+        "멤버 목록 갱신은 스태프만 할 수 있습니다.",
+    ),
+    (
+        "error.not_implemented",
+        "`{command}` 명령어는 아직 지원하지 않습니다.",
+    ),
+    (
+        "error.timeout",
+        "`{command}` 명령이 제한 시간 안에 끝나지 않았습니다. 잠시 후 다시 시도하거나 스태프에게 문의해 주세요.",
+    ),
+    ("reboot.failed", "재부팅에 실패했습니다."),
+    (
+        "home.reset.failed",
+        "home 초기화에 실패했습니다. 스태프에게 문의해 주세요.",
+    ),
+    (
+        "home.reset.partial",
+        "home이 삭제되었지만 다시 만들지 못했습니다. 스태프에게 알렸습니다.",
+    ),
+    (
+        "home.reset.unverified",
+        "home을 다시 만들었지만 상태를 확인하지 못했습니다. 스태프에게 알렸습니다.",
+    ),
+    ("home.close.failed", "home 연결 해제에 실패했습니다."),
+    ("goinfre.reset.failed", "goinfre 초기화에 실패했습니다."),
+    (
+        "update.member_list_failed",
+        "채널 멤버 목록을 가져오지 못했습니다.",
+    ),
+    (
+        "update.store_failed",
+        "멤버 목록을 저장하지 못했습니다.",
+    ),
+    (
+        "update.done",
+        "멤버 목록을 갱신했습니다. 추가 {added}, 변경 {changed}, 삭제 {removed}",
+    ),
+    ("link.sent", "DM으로 계정 연결 링크를 보냈습니다."),
+    ("link.dm_failed", "DM을 보내지 못했습니다."),
+    (
+        "link.dm",
+        "아래 링크에서 42 인트라에 로그인하면 계정이 연결됩니다. 링크는 10분 동안 유효합니다.\n{url}",
+    ),
+    (
+        "link.done",
+        "`{login}` 계정이 연결되었습니다. 이 창을 닫아도 됩니다.",
+    ),
+    (
+        "link.expired",
+        "만료되었거나 이미 사용한 링크입니다. 다시 요청해 주세요.",
+    ),
+    ("link.denied", "42 인트라에서 인증이 취소되었습니다."),
+    ("command.done", "명령을 실행했습니다."),
+    (
+        "job.queued",
+        "다른 작업이 끝나기를 기다리고 있습니다. 대기 순서: {position}",
+    ),
+    (
+        "job.interrupted_not_started",
+        "봇이 재시작되어 `{command}` 요청이 실행되지 않았습니다. 다시 보내 주세요.",
+    ),
+    (
+        "job.interrupted_running",
+        "봇이 재시작되어 `{command}` 실행이 중간에 멈췄습니다. 스태프에게 알렸습니다.",
+    ),
+    (
+        "staff.job_interrupted",
+        ":warning: 재시작으로 <@{user}>의 `{command}` 실행이 중단되었습니다. 현재 상태: {state}",
+    ),
+    (
+        "confirm.prompt",
+        "`{command}`은(는) 되돌릴 수 없습니다. 계속하시겠습니까?",
+    ),
+    ("confirm.button", "실행"),
+    ("confirm.cancel_button", "취소"),
+    ("confirm.accepted", "`{command}` 실행을 확인했습니다."),
+    ("confirm.cancelled", "취소되었습니다."),
+    (
+        "confirm.not_requester",
+        "명령을 요청한 사람만 확인할 수 있습니다.",
+    ),
+    (
+        "confirm.expired",
+        "확인 시간이 지났습니다. 명령을 다시 보내 주세요.",
+    ),
+    (
+        "link.failed",
+        "계정을 연결하지 못했습니다. 잠시 후 다시 시도해 주세요.",
+    ),
+    (
+        "staff.home_reset_partial",
+        ":warning: `{login}`의 home 초기화가 `{step}` 단계에서 중단되었습니다. 완료된 단계: {completed}, 이전 상태: {state}, 오류: {error}. home을 직접 다시 만들어 주세요.",
+    ),
+    (
+        "staff.home_reset_unverified",
+        ":warning: `{login}`의 home을 다시 만들었지만 상태를 확인하지 못했습니다. 오류: {error}. home 상태를 확인해 주세요.",
+    ),
+];
+
+const EN: &[(&str, &str)] = &[
+    ("help.gsctl", "Manage cluster PCs and student homes."),
+    ("help.reboot", "Reboot the PC you are logged in to."),
+    (
+        "help.reboot.host",
+        "Host to reboot instead of your own PC (staff only).",
+    ),
+    ("help.home", "Manage your home directory."),
+    ("help.home.reset", "Reset your home to its default state."),
+    (
+        "help.home.close",
+        "Disconnect your remote home from the PC.",
+    ),
+    (
+        "help.goinfre",
+        "Manage the goinfre of the PC you are logged in to.",
+    ),
+    ("help.goinfre.reset", "Wipe your goinfre."),
+    ("help.update", "Refresh the channel member list."),
+    ("help.link", "Link your 42 intra account to your Slack account."),
+    ("help.flag.help", "Show this help message."),
+    ("help.usage", "Usage: {usage}"),
+    ("help.commands", "Commands"),
+    ("help.arguments", "Arguments"),
+    ("help.options", "Options"),
+    (
+        "help.more",
+        "Run `{command} --help` for more information on a command.",
+    ),
+    ("parse.missing_command", "A command is required."),
+    ("parse.missing_subcommand", "`{path}` needs a subcommand."),
+    ("parse.unknown_command", "Unknown command `{token}`."),
+    (
+        "parse.unknown_subcommand",
+        "Unknown subcommand `{token}` for `{path}`.",
+    ),
+    ("parse.unknown_flag", "Unknown flag `{token}` for `{path}`."),
+    (
+        "parse.unexpected_argument",
+        "Unexpected argument `{token}` for `{path}`.",
+    ),
+    (
+        "parse.missing_argument",
+        "`{path}` needs a <{arg}> argument.",
+    ),
+    (
+        "parse.invalid_argument",
+        "`{token}` is not a valid <{arg}> for `{path}`.",
+    ),
+    (
+        "error.command_failed",
+        "Command cannot be executed for the following reasons: {reason}",
+    ),
+    ("error.location_not_found", "Location not found!"),
+    (
+        "error.location_ambiguous",
+        "You are logged in on several hosts: {hosts}. Please log out from the ones you are not using.",
+    ),
+    (
+        "error.location_unavailable",
+        "Could not reach the 42 API to find your location. Please try again later.",
+    ),
+    (
+        "error.sender_unknown",
+        "Could not look you up on Slack, so the request was not run. Please try again later.",
+    ),
+    (
+        "error.staff_only_reboot",
+        "Only staff can reboot another host.",
+    ),
+    (
+        "error.not_registered",
+        "Your Slack account is not linked to a 42 login. Run `{command}` to link it.",
+    ),
+    (
+        "error.not_linked",
+        "This command needs a verified 42 account. Run `{command}` to link it.",
+    ),
+    (
+        "error.staff_only_update",
+        "Only staff can update the member list.",
+    ),
+    ("error.not_implemented", "`{command}` is not implemented."),
+    (
+        "error.timeout",
+        "`{command}` did not finish in time. Please try again later or contact staff.",
+    ),
+    ("reboot.failed", "Reboot failed."),
+    (
+        "home.reset.failed",
+        "Home reset failed. please contact staff",
+    ),
+    (
+        "home.reset.partial",
+        "Your home was deleted but could not be recreated. Staff have been notified.",
+    ),
+    (
+        "home.reset.unverified",
+        "Your home was recreated but its state could not be confirmed. Staff have been notified.",
+    ),
+    ("home.close.failed", "Home close failed."),
+    ("goinfre.reset.failed", "Goinfre reset failed."),
+    ("update.member_list_failed", "get member list failed."),
+    ("update.store_failed", "Could not save the member list."),
+    (
+        "update.done",
+        "Member list updated: {added} added, {changed} changed, {removed} removed.",
+    ),
+    ("link.sent", "I sent you a link in a DM."),
+    ("link.dm_failed", "Could not send you a DM."),
+    (
+        "link.dm",
+        "Log in to 42 intra with the link below to link your account. The link is valid for 10 minutes.\n{url}",
+    ),
+    (
+        "link.done",
+        "Linked to `{login}`. You can close this window.",
+    ),
+    (
+        "link.expired",
+        "This link has expired or was already used. Please ask for a new one.",
+    ),
+    ("link.denied", "Authorization was cancelled on 42 intra."),
+    ("command.done", "Done."),
+    (
+        "job.queued",
+        "Queued, position {position}. It will run when the jobs ahead of it finish.",
+    ),
+    (
+        "job.interrupted_not_started",
+        "The bot restarted before `{command}` could run. Please send it again.",
+    ),
+    (
+        "job.interrupted_running",
+        "The bot restarted while `{command}` was running. Staff have been notified.",
+    ),
+    (
+        "staff.job_interrupted",
+        ":warning: A restart interrupted `{command}` for <@{user}>. Current state: {state}",
+    ),
+    (
+        "confirm.prompt",
+        "`{command}` cannot be undone. Do you want to continue?",
+    ),
+    ("confirm.button", "Confirm"),
+    ("confirm.cancel_button", "Cancel"),
+    ("confirm.accepted", "Confirmed `{command}`."),
+    ("confirm.cancelled", "Cancelled."),
+    (
+        "confirm.not_requester",
+        "Only the person who sent the command can confirm it.",
+    ),
+    (
+        "confirm.expired",
+        "This confirmation has expired. Please send the command again.",
+    ),
+    (
+        "link.failed",
+        "Could not link your account. Please try again later.",
+    ),
+    (
+        "staff.home_reset_partial",
+        ":warning: Home reset of `{login}` stopped at `{step}`. Completed: {completed}, previous state: {state}, error: {error}. The home needs to be recreated by hand.",
+    ),
+    (
+        "staff.home_reset_unverified",
+        ":warning: Home of `{login}` was recreated but its state could not be read back. Error: {error}. Please check the home.",
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn catalogs_have_the_same_keys() {
+        for (key, _) in KO {
+            assert!(Locale::En.lookup(key).is_some(), "missing en: {key}");
+        }
+        for (key, _) in EN {
+            assert!(Locale::Ko.lookup(key).is_some(), "missing ko: {key}");
+        }
+    }
+
+    #[test]
+    fn tr_fills_parameters() {
+        assert_eq!(
+            tr(
+                Locale::En,
+                "parse.unknown_subcommand",
+                &[("token", "{path}"), ("path", "home")]
+            ),
+            "Unknown subcommand `{path}` for `home`."
+        );
+    }
+
+    #[test]
+    fn tr_falls_back_to_key() {
+        assert_eq!(tr(Locale::Ko, "no.such.key", &[]), "no.such.key");
+    }
+
+    #[test]
+    fn locale_from_slack_tag() {
+        assert_eq!(Locale::parse("ko-KR"), Some(Locale::Ko));
+        assert_eq!(Locale::parse("en-US"), Some(Locale::En));
+        assert_eq!(Locale::parse("fr-FR"), None);
+    }
+}
@@ -0,0 +1,330 @@
+use ft_api::FtLoginId;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::oneshot;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::bot_cmd::{GsctlCommand, SlackRequest};
+use crate::error::Action;
+use crate::homemaker::{HomeStatus, HomesApi};
+use crate::i18n::Locale;
+use crate::user::persist;
+
+const FINISHED_RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Received,
+    Parsed,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl JobState {
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobState::Succeeded | JobState::Failed)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub id: String,
+    pub state: JobState,
+    pub request: SlackRequest,
+    pub locale: Option<Locale>,
+    pub action: Option<Action>,
+    pub login: Option<FtLoginId>,
+    pub error: Option<String>,
+    // Seconds since the unix epoch.
+    pub updated_at: u64,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default()
+}
+
+// Every command's lifecycle, written to disk at each step so a restart can
+// tell which jobs never got an answer. Finished jobs are kept for a day.
+#[derive(Debug)]
+pub struct JobStore {
+    jobs: Mutex<Versioned>,
+    writes: mpsc::Sender<Write>,
+    writer: Option<thread::JoinHandle<()>>,
+}
+
+#[derive(Debug)]
+struct Versioned {
+    version: u64,
+    jobs: BTreeMap<String, JobRecord>,
+}
+
+#[derive(Debug)]
+enum Write {
+    Save(u64, BTreeMap<String, JobRecord>),
+    Flush(oneshot::Sender<()>),
+    Stop,
+}
+
+// Saves snapshots one at a time, off the async workers. Snapshots taken by
+// racing updates can arrive out of order, older ones are dropped.
+fn write_jobs(path: PathBuf, writes: mpsc::Receiver<Write>) {
+    let mut written = 0;
+    while let Ok(write) = writes.recv() {
+        match write {
+            Write::Save(version, _) if version <= written => {}
+            Write::Save(version, jobs) => match persist(&path, &jobs) {
+                Ok(()) => written = version,
+                Err(err) => error!("Could not save jobs: {err}"),
+            },
+            Write::Flush(done) => {
+                let _ = done.send(());
+            }
+            Write::Stop => break,
+        }
+    }
+}
+
+impl JobStore {
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let mut jobs: BTreeMap<String, JobRecord> = match fs::read(&path) {
+            Ok(raw) => serde_json::from_slice(&raw)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(err) => return Err(err),
+        };
+
+        let cutoff = now_secs().saturating_sub(FINISHED_RETENTION.as_secs());
+        jobs.retain(|_, job| !job.state.is_finished() || job.updated_at >= cutoff);
+
+        let (writes, queued) = mpsc::channel();
+        let writer = thread::Builder::new()
+            .name("job-store".to_string())
+            .spawn(move || write_jobs(path, queued))?;
+
+        Ok(JobStore {
+            jobs: Mutex::new(Versioned { version: 0, jobs }),
+            writes,
+            writer: Some(writer),
+        })
+    }
+
+    pub fn received(&self, request: &SlackRequest) -> String {
+        let id = Uuid::new_v4().simple().to_string();
+        let job = JobRecord {
+            id: id.clone(),
+            state: JobState::Received,
+            request: request.clone(),
+            locale: None,
+            action: None,
+            login: None,
+            error: None,
+            updated_at: now_secs(),
+        };
+        self.update(|jobs| {
+            jobs.insert(id.clone(), job);
+        });
+        id
+    }
+
+    pub fn parsed(&self, id: &str, locale: Locale, command: &GsctlCommand) {
+        self.update(|jobs| {
+            if let Some(job) = jobs.get_mut(id) {
+                job.state = JobState::Parsed;
+                job.locale = Some(locale);
+                job.action = command.action();
+                job.login = command.login().cloned();
+                job.updated_at = now_secs();
+            }
+        });
+    }
+
+    pub fn running(&self, id: &str) {
+        self.set_state(id, JobState::Running, None);
+    }
+
+    pub fn finished(&self, id: &str, error: Option<String>) {
+        let state = match error {
+            Some(_) => JobState::Failed,
+            None => JobState::Succeeded,
+        };
+        self.set_state(id, state, error);
+    }
+
+    // For messages that turned out not to be a job, or that handed over to
+    // a confirmation prompt.
+    pub fn discard(&self, id: &str) {
+        self.update(|jobs| {
+            jobs.remove(id);
+        });
+    }
+
+    pub fn unfinished(&self) -> Vec<JobRecord> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .jobs
+            .values()
+            .filter(|job| !job.state.is_finished())
+            .cloned()
+            .collect()
+    }
+
+    fn set_state(&self, id: &str, state: JobState, error: Option<String>) {
+        self.update(|jobs| {
+            if let Some(job) = jobs.get_mut(id) {
+                job.state = state;
+                job.error = error;
+                job.updated_at = now_secs();
+            }
+        });
+    }
+
+    // Resolves once every change made so far has been written.
+    pub async fn flush(&self) {
+        let (done, written) = oneshot::channel();
+        if self.writes.send(Write::Flush(done)).is_ok() {
+            let _ = written.await;
+        }
+    }
+
+    // The job runs whether or not its record could be saved, so the write
+    // is left to the writer thread, which only logs a failure.
+    fn update(&self, change: impl FnOnce(&mut BTreeMap<String, JobRecord>)) {
+        let (version, snapshot) = {
+            let mut current = self.jobs.lock().unwrap();
+            change(&mut current.jobs);
+            current.version += 1;
+            (current.version, current.jobs.clone())
+        };
+        let _ = self.writes.send(Write::Save(version, snapshot));
+    }
+}
+
+// Waits for pending writes, so nothing is lost when the store goes away.
+impl Drop for JobStore {
+    fn drop(&mut self) {
+        let _ = self.writes.send(Write::Stop);
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Interrupted {
+    // Stopped before anything ran, sending the command again is safe.
+    NotStarted,
+    // Stopped while running. For home commands the home was checked again.
+    Running {
+        home: Option<Result<HomeStatus, String>>,
+    },
+}
+
+// Closes jobs a previous run left open and says what is known about each.
+pub async fn reconcile(
+    jobs: &JobStore,
+    homes: &dyn HomesApi,
+    wakeup_word: &str,
+) -> Vec<(JobRecord, Interrupted)> {
+    let mut reconciled = vec![];
+
+    for job in jobs.unfinished() {
+        jobs.finished(&job.id, Some("interrupted by a restart".to_string()));
+        let interrupted = match job.state {
+            // Only messages meant for the bot are worth an answer.
+            JobState::Received if job.request.command_text(wakeup_word).is_none() => continue,
+            JobState::Running => {
+                let home = match (&job.action, &job.login) {
+                    (Some(Action::HomeReset | Action::HomeClose), Some(login)) => {
+                        Some(homes.status(login).await.map_err(|err| err.to_string()))
+                    }
+                    _ => None,
+                };
+                Interrupted::Running { home }
+            }
+            _ => Interrupted::NotStarted,
+        };
+        reconciled.push((job, interrupted));
+    }
+
+    reconciled
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot_cmd::{MessageSource, SubCommand};
+    use crate::homemaker::ScriptedHomes;
+    use crate::test_util::store_path;
+    use slack_morphism::prelude::*;
+
+    fn request(text: &str) -> SlackRequest {
+        SlackRequest {
+            user: SlackUserId("U1".to_string()),
+            channel: SlackChannelId("C1".to_string()),
+            source: MessageSource::Mention {
+                ts: SlackTs("1".to_string()),
+                thread_ts: None,
+            },
+            text: text.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn restart_reconciles_unfinished_jobs() {
+        let path = store_path("jobs");
+        let jobs = JobStore::open(&path).unwrap();
+        let reset = GsctlCommand::Home(Some(SubCommand::Reset(FtLoginId("student".into()))));
+
+        let done = jobs.received(&request("reboot"));
+        jobs.finished(&done, None);
+        jobs.received(&request("thanks"));
+        let unparsed = jobs.received(&request("<@BOT> reboot"));
+        let queued = jobs.received(&request("home reset"));
+        jobs.parsed(&queued, Locale::En, &reset);
+        let running = jobs.received(&request("home reset"));
+        jobs.parsed(&running, Locale::En, &reset);
+        jobs.running(&running);
+        drop(jobs);
+
+        let jobs = JobStore::open(&path).unwrap();
+        let homes = ScriptedHomes::new();
+        homes.push_status(HomeStatus {
+            state: "deleted".to_string(),
+            initiator: None,
+        });
+        let reconciled = reconcile(&jobs, &homes, "<@BOT>").await;
+        jobs.flush().await;
+
+        assert_eq!(reconciled.len(), 3);
+        let state_of = |id: &str| {
+            reconciled
+                .iter()
+                .find(|(job, _)| job.id == id)
+                .map(|(_, interrupted)| interrupted)
+                .unwrap()
+        };
+        assert!(matches!(state_of(&unparsed), Interrupted::NotStarted));
+        assert!(matches!(state_of(&queued), Interrupted::NotStarted));
+        assert!(matches!(
+            state_of(&running),
+            Interrupted::Running { home: Some(Ok(status)) } if status.state == "deleted"
+        ));
+        assert_eq!(homes.calls(), vec!["status student"]);
+        assert!(JobStore::open(&path).unwrap().unfinished().is_empty());
+        fs::remove_file(path).unwrap();
+    }
+}
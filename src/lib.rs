@@ -1,9 +1,26 @@
 pub mod bot_cmd;
+pub mod campus;
+pub mod config;
+pub mod confirm;
+pub mod dedup;
+pub mod dispatch;
+pub mod error;
 pub mod excutor;
+pub mod grammar;
 pub mod handler;
+pub mod home_reset;
+pub mod homemaker;
+pub mod i18n;
+pub mod jobs;
+pub mod link;
+pub mod location;
+pub mod reply;
+pub mod scheduler;
 pub mod server;
+pub mod socket_mode;
+pub mod staff;
+#[cfg(test)]
+mod test_util;
+pub mod timeouts;
 pub mod user;
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
-
-pub const WAKEUP_WORD: &str = "YourSlackBotInternalIdHere";
-pub const WAKEUP_WORD_FOR_USER: &str = "@YourSlackBotNameHere";
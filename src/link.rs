@@ -0,0 +1,207 @@
+use ft_api::FtLoginId;
+use reqwest::Client;
+use serde::Deserialize;
+use slack_morphism::SlackUserId;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use url::Url;
+use uuid::Uuid;
+
+use crate::i18n::Locale;
+
+const FT_AUTHORIZE_URL: &str = "https://api.intra.42.fr/oauth/authorize";
+const FT_TOKEN_URL: &str = "https://api.intra.42.fr/oauth/token";
+const FT_ME_URL: &str = "https://api.intra.42.fr/v2/me";
+const LINK_EXPIRY: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug)]
+pub enum LinkError {
+    UnknownState,
+    Expired,
+    Transport(reqwest::Error),
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::UnknownState => write!(f, "unknown link state"),
+            LinkError::Expired => write!(f, "link request expired"),
+            LinkError::Transport(err) => write!(f, "42 oauth failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+impl From<reqwest::Error> for LinkError {
+    fn from(err: reqwest::Error) -> Self {
+        LinkError::Transport(err)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingLink {
+    pub slack_id: SlackUserId,
+    pub locale: Locale,
+    expires_at: Instant,
+}
+
+#[derive(Debug, Default)]
+pub struct PendingLinks {
+    states: Mutex<HashMap<String, PendingLink>>,
+}
+
+impl PendingLinks {
+    pub fn insert(&self, slack_id: SlackUserId, locale: Locale, now: Instant) -> String {
+        let state = Uuid::new_v4().simple().to_string();
+        let mut states = self.states.lock().unwrap();
+
+        states.retain(|_, link| link.expires_at > now && link.slack_id != slack_id);
+        states.insert(
+            state.clone(),
+            PendingLink {
+                slack_id,
+                locale,
+                expires_at: now + LINK_EXPIRY,
+            },
+        );
+        state
+    }
+
+    // A state can only be used once, whether or not it is still valid.
+    pub fn take(&self, state: &str, now: Instant) -> Result<PendingLink, LinkError> {
+        match self.states.lock().unwrap().remove(state) {
+            Some(link) if link.expires_at > now => Ok(link),
+            Some(_) => Err(LinkError::Expired),
+            None => Err(LinkError::UnknownState),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Me {
+    login: FtLoginId,
+}
+
+pub struct AccountLinker {
+    http: Client,
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+    pub pending: PendingLinks,
+}
+
+impl fmt::Debug for AccountLinker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccountLinker")
+            .field("client_id", &self.client_id)
+            .field("client_secret", &"<redacted>")
+            .field("redirect_uri", &self.redirect_uri)
+            .finish()
+    }
+}
+
+impl AccountLinker {
+    pub fn new(client_id: String, client_secret: String, redirect_uri: String) -> Self {
+        AccountLinker {
+            http: Client::new(),
+            client_id,
+            client_secret,
+            redirect_uri,
+            pending: PendingLinks::default(),
+        }
+    }
+
+    pub fn authorize_url(&self, state: &str) -> Url {
+        let mut url = Url::parse(FT_AUTHORIZE_URL).unwrap();
+        url.query_pairs_mut()
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_uri)
+            .append_pair("response_type", "code")
+            .append_pair("scope", "public")
+            .append_pair("state", state);
+        url
+    }
+
+    pub fn start(&self, slack_id: SlackUserId, locale: Locale) -> Url {
+        let state = self.pending.insert(slack_id, locale, Instant::now());
+        self.authorize_url(&state)
+    }
+
+    pub async fn verify(&self, code: &str) -> Result<FtLoginId, LinkError> {
+        let token: TokenResponse = self
+            .http
+            .post(FT_TOKEN_URL)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("client_id", &self.client_id),
+                ("client_secret", &self.client_secret),
+                ("code", code),
+                ("redirect_uri", &self.redirect_uri),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let me: Me = self
+            .http
+            .get(FT_ME_URL)
+            .bearer_auth(token.access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(me.login)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_is_single_use_and_expires() {
+        let pending = PendingLinks::default();
+        let now = Instant::now();
+        let slack_id = SlackUserId("U1".to_string());
+
+        let state = pending.insert(slack_id.clone(), Locale::En, now);
+        assert_eq!(pending.take(&state, now).unwrap().slack_id, slack_id);
+        assert!(matches!(
+            pending.take(&state, now),
+            Err(LinkError::UnknownState)
+        ));
+
+        let state = pending.insert(slack_id, Locale::En, now);
+        assert!(matches!(
+            pending.take(&state, now + LINK_EXPIRY),
+            Err(LinkError::Expired)
+        ));
+    }
+
+    #[test]
+    fn authorize_url_carries_state() {
+        let linker = AccountLinker::new(
+            "uid".into(),
+            "secret".into(),
+            "https://bot.example/link".into(),
+        );
+
+        let url = linker.authorize_url("abc");
+
+        assert!(url.as_str().starts_with(FT_AUTHORIZE_URL));
+        assert!(url.query_pairs().any(|(k, v)| k == "state" && v == "abc"));
+        assert!(!url.as_str().contains("secret"));
+    }
+}
@@ -0,0 +1,119 @@
+use ft_api::{FtHost, FtLocation, FtLoginId};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::campus::CampusApi;
+use crate::i18n::{tr, Locale};
+
+#[derive(Debug)]
+pub enum LocationError {
+    NotLoggedIn(FtLoginId),
+    Ambiguous(FtLoginId, Vec<FtHost>),
+    ApiUnavailable(crate::Error),
+}
+
+impl fmt::Display for LocationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LocationError::NotLoggedIn(login) => write!(f, "{login} is not logged in anywhere"),
+            LocationError::Ambiguous(login, hosts) => {
+                let hosts: Vec<_> = hosts.iter().map(|host| host.0.as_str()).collect();
+                write!(f, "{login} is logged in on {}", hosts.join(", "))
+            }
+            LocationError::ApiUnavailable(err) => write!(f, "42 API unavailable: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for LocationError {}
+
+impl LocationError {
+    pub fn message(&self, locale: Locale) -> String {
+        match self {
+            LocationError::NotLoggedIn(_) => tr(locale, "error.location_not_found", &[]),
+            LocationError::Ambiguous(_, hosts) => {
+                let hosts: Vec<_> = hosts.iter().map(|host| host.0.as_str()).collect();
+                tr(
+                    locale,
+                    "error.location_ambiguous",
+                    &[("hosts", &hosts.join(", "))],
+                )
+            }
+            LocationError::ApiUnavailable(_) => tr(locale, "error.location_unavailable", &[]),
+        }
+    }
+}
+
+pub struct LocationResolver {
+    campus: Arc<CampusApi>,
+}
+
+impl LocationResolver {
+    pub fn new(campus: Arc<CampusApi>) -> Self {
+        LocationResolver { campus }
+    }
+
+    pub async fn resolve(&self, login: &FtLoginId) -> Result<FtHost, LocationError> {
+        let locations = self
+            .campus
+            .active_locations()
+            .await
+            .map_err(LocationError::ApiUnavailable)?;
+
+        host_of(&locations, login)
+    }
+}
+
+fn host_of(locations: &[FtLocation], login: &FtLoginId) -> Result<FtHost, LocationError> {
+    let mut hosts: Vec<FtHost> = vec![];
+    for location in locations {
+        if location.user.login.as_ref() == Some(login) && !hosts.contains(&location.host) {
+            hosts.push(location.host.clone());
+        }
+    }
+
+    match hosts.len() {
+        0 => Err(LocationError::NotLoggedIn(login.clone())),
+        1 => Ok(hosts.remove(0)),
+        _ => Err(LocationError::Ambiguous(login.clone(), hosts)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ft_api::FtUser;
+
+    fn location(host: &str, login: Option<&str>) -> FtLocation {
+        FtLocation {
+            host: FtHost(host.to_string()),
+            user: FtUser {
+                login: login.map(|login| FtLoginId(login.to_string())),
+            },
+        }
+    }
+
+    #[test]
+    fn host_of_distinguishes_outcomes() {
+        let locations = vec![
+            location("c1r1s1", Some("student")),
+            location("c1r1s2", Some("twice")),
+            location("c1r1s3", Some("twice")),
+            location("c1r1s4", None),
+        ];
+        let login = |login: &str| FtLoginId(login.to_string());
+
+        assert_eq!(
+            host_of(&locations, &login("student")).unwrap(),
+            FtHost("c1r1s1".to_string())
+        );
+        assert!(matches!(
+            host_of(&locations, &login("nobody")),
+            Err(LocationError::NotLoggedIn(_))
+        ));
+        assert!(matches!(
+            host_of(&locations, &login("twice")),
+            Err(LocationError::Ambiguous(_, hosts)) if hosts.len() == 2
+        ));
+    }
+}
@@ -1,3 +1,4 @@
+use gs_slack_bot::config::Config;
 use gs_slack_bot::server::*;
 
 #[tokio::main]
@@ -8,7 +9,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         .finish();
     tracing::subscriber::set_global_default(subscriber)?;
 
-    run_slack_server().await?;
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => {
+            tracing::error!("{err}");
+            std::process::exit(1);
+        }
+    };
+    run_slack_server(config).await?;
 
     Ok(())
 }
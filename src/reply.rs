@@ -0,0 +1,223 @@
+use slack_morphism::prelude::*;
+use tracing::debug;
+
+use crate::bot_cmd::{MessageSource, SlackMessageContext};
+use crate::confirm::{CANCEL_ACTION, CONFIRM_ACTION};
+use crate::error::{Action, BotError};
+use crate::i18n::{tr, Locale};
+
+const LOADING: &str = "gsroot-loading";
+const DONE: &str = "white_check_mark";
+const FAILED: &str = "x";
+
+pub type Session<'a> = SlackClientSession<'a, SlackClientHyperHttpsConnector>;
+
+async fn swap_reaction(
+    session: &Session<'_>,
+    context: &SlackMessageContext,
+    ts: &SlackTs,
+    from: Option<&str>,
+    to: &str,
+) {
+    if let Some(from) = from {
+        let _ = session
+            .reactions_remove(
+                &SlackApiReactionsRemoveRequest::new(SlackReactionName::new(from.to_owned()))
+                    .with_channel(context.channel.clone())
+                    .with_timestamp(ts.clone()),
+            )
+            .await;
+    }
+    let _ = session
+        .reactions_add(&SlackApiReactionsAddRequest::new(
+            context.channel.clone(),
+            SlackReactionName::new(to.to_owned()),
+            ts.clone(),
+        ))
+        .await;
+}
+
+pub async fn mark_running(session: &Session<'_>, context: &SlackMessageContext) {
+    if let MessageSource::Mention { ts, .. } | MessageSource::DirectMessage { ts, .. } =
+        &context.source
+    {
+        swap_reaction(session, context, ts, None, LOADING).await;
+    }
+}
+
+// Mentions and DMs are answered in their thread. Slash commands go back
+// through the response url, which only the caller can see.
+async fn post(
+    client: &SlackHyperClient,
+    session: &Session<'_>,
+    context: &SlackMessageContext,
+    content: SlackMessageContent,
+) {
+    match &context.source {
+        MessageSource::Mention { ts, thread_ts }
+        | MessageSource::DirectMessage { ts, thread_ts } => {
+            let _ = session
+                .chat_post_message(
+                    &SlackApiChatPostMessageRequest::new(context.channel.clone(), content)
+                        .with_thread_ts(thread_ts.as_ref().unwrap_or(ts).clone()),
+                )
+                .await;
+        }
+        MessageSource::SlashCommand { response_url } => {
+            let res = client
+                .respond_to_event(
+                    response_url,
+                    &SlackApiPostWebhookMessageRequest::new(content),
+                )
+                .await;
+            if let Err(err) = res {
+                debug!("Could not answer slash command `{}`: {err}", context.text);
+            }
+        }
+    }
+}
+
+// Mentions and DMs also get a reaction. A slash command has nothing to react
+// to, so it always gets a reply.
+pub async fn report(
+    client: &SlackHyperClient,
+    session: &Session<'_>,
+    context: &SlackMessageContext,
+    wakeup_word_for_user: &str,
+    result: Result<Option<String>, Option<BotError>>,
+) {
+    let (succeeded, message) = match result {
+        Ok(message) => (true, message),
+        Err(Some(err)) => {
+            err.log(&context.text);
+            (
+                false,
+                Some(err.message(context.locale, wakeup_word_for_user)),
+            )
+        }
+        Err(None) => return,
+    };
+
+    let message = match &context.source {
+        MessageSource::Mention { ts, .. } | MessageSource::DirectMessage { ts, .. } => {
+            let reaction = if succeeded { DONE } else { FAILED };
+            swap_reaction(session, context, ts, Some(LOADING), reaction).await;
+            message
+        }
+        MessageSource::SlashCommand { .. } => {
+            Some(message.unwrap_or_else(|| tr(context.locale, "command.done", &[])))
+        }
+    };
+
+    if let Some(message) = message {
+        post(
+            client,
+            session,
+            context,
+            SlackMessageContent::new().with_text(message),
+        )
+        .await;
+    }
+}
+
+pub async fn notice(
+    client: &SlackHyperClient,
+    session: &Session<'_>,
+    context: &SlackMessageContext,
+    text: String,
+) {
+    post(
+        client,
+        session,
+        context,
+        SlackMessageContent::new().with_text(text),
+    )
+    .await;
+}
+
+// A job cut off by a shutdown. Mentions and DMs lose their loading
+// reaction, so nothing looks like it is still running.
+pub async fn interrupted(
+    client: &SlackHyperClient,
+    session: &Session<'_>,
+    context: &SlackMessageContext,
+    text: String,
+) {
+    if let MessageSource::Mention { ts, .. } | MessageSource::DirectMessage { ts, .. } =
+        &context.source
+    {
+        swap_reaction(session, context, ts, Some(LOADING), FAILED).await;
+    }
+    notice(client, session, context, text).await;
+}
+
+pub async fn queued(
+    client: &SlackHyperClient,
+    session: &Session<'_>,
+    context: &SlackMessageContext,
+    position: usize,
+) {
+    let text = tr(
+        context.locale,
+        "job.queued",
+        &[("position", &position.to_string())],
+    );
+    notice(client, session, context, text).await;
+}
+
+fn confirmation_prompt(locale: Locale, action: Action, id: &str) -> SlackMessageContent {
+    let text = tr(locale, "confirm.prompt", &[("command", action.name())]);
+    let button = |action_id: &str, key: &str| {
+        SlackBlockButtonElement::new(tr(locale, key, &[]).into())
+            .with_action_id(SlackActionId::new(action_id.to_owned()))
+            .with_value(id.to_owned())
+    };
+
+    SlackMessageContent::new()
+        .with_text(text.clone())
+        .with_blocks(vec![
+            SlackSectionBlock::new()
+                .with_text(SlackBlockText::MarkDown(SlackBlockMarkDownText::new(text)))
+                .into(),
+            SlackActionsBlock::new(vec![
+                button(CONFIRM_ACTION, "confirm.button")
+                    .with_style(SlackBlockButtonStyle::Danger)
+                    .into(),
+                button(CANCEL_ACTION, "confirm.cancel_button").into(),
+            ])
+            .into(),
+        ])
+}
+
+// Posts Confirm/Cancel buttons where the command was sent. The buttons carry
+// `id`, the key of the task waiting in `PendingConfirmations`.
+pub async fn ask_confirmation(
+    client: &SlackHyperClient,
+    session: &Session<'_>,
+    context: &SlackMessageContext,
+    action: Action,
+    id: &str,
+) {
+    let content = confirmation_prompt(context.locale, action, id);
+    post(client, session, context, content).await;
+}
+
+// Answers a button click. `replace` swaps out the prompt so its buttons
+// cannot be clicked again, otherwise only the clicker sees `text`.
+pub async fn answer_click(
+    client: &SlackHyperClient,
+    response_url: &SlackResponseUrl,
+    text: String,
+    replace: bool,
+) {
+    let res = client
+        .respond_to_event(
+            response_url,
+            &SlackApiPostWebhookMessageRequest::new(SlackMessageContent::new().with_text(text))
+                .with_replace_original(replace),
+        )
+        .await;
+    if let Err(err) = res {
+        debug!("Could not answer button click: {err}");
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Mutex as KeyLock, OwnedMutexGuard, OwnedSemaphorePermit, Semaphore};
+
+#[derive(Debug, Default)]
+struct KeyState {
+    // Jobs holding or waiting for the lock.
+    jobs: usize,
+    lock: Arc<KeyLock<()>>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    max_jobs: usize,
+    slots: Arc<Semaphore>,
+    jobs: Mutex<(usize, HashMap<String, KeyState>)>,
+}
+
+// Runs at most `max_jobs` commands at once, and one at a time per key so two
+// commands never touch the same host or login concurrently.
+#[derive(Debug, Clone)]
+pub struct Scheduler {
+    inner: Arc<Inner>,
+}
+
+impl Scheduler {
+    pub fn new(max_jobs: usize) -> Self {
+        let max_jobs = max_jobs.max(1);
+        Scheduler {
+            inner: Arc::new(Inner {
+                max_jobs,
+                slots: Arc::new(Semaphore::new(max_jobs)),
+                jobs: Mutex::new((0, HashMap::new())),
+            }),
+        }
+    }
+
+    // Registers a job. `Ticket::position` tells how many jobs are ahead of it.
+    pub fn enqueue(&self, mut keys: Vec<String>) -> Ticket {
+        // A fixed order keeps two multi-key jobs from deadlocking.
+        keys.sort();
+        keys.dedup();
+
+        let mut jobs = self.inner.jobs.lock().unwrap();
+        let (total, per_key) = &mut *jobs;
+
+        let waiting_for_slot = total.saturating_sub(self.inner.max_jobs - 1);
+        let mut position = waiting_for_slot;
+        let locks = keys
+            .iter()
+            .map(|key| {
+                let state = per_key.entry(key.clone()).or_default();
+                position = position.max(state.jobs);
+                state.jobs += 1;
+                state.lock.clone()
+            })
+            .collect();
+        *total += 1;
+
+        Ticket {
+            registration: Registration {
+                inner: self.inner.clone(),
+                keys,
+            },
+            locks,
+            position,
+        }
+    }
+}
+
+// Unregisters the job however it ends, including a dropped wait.
+#[derive(Debug)]
+struct Registration {
+    inner: Arc<Inner>,
+    keys: Vec<String>,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut jobs = self.inner.jobs.lock().unwrap();
+        let (total, per_key) = &mut *jobs;
+        *total -= 1;
+        for key in &self.keys {
+            if let Some(state) = per_key.get_mut(key) {
+                state.jobs -= 1;
+                if state.jobs == 0 {
+                    per_key.remove(key);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Ticket {
+    registration: Registration,
+    locks: Vec<Arc<KeyLock<()>>>,
+    position: usize,
+}
+
+impl Ticket {
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub async fn wait(self) -> JobPermit {
+        let mut guards = Vec::with_capacity(self.locks.len());
+        for lock in self.locks {
+            guards.push(lock.lock_owned().await);
+        }
+        let slot = self
+            .registration
+            .inner
+            .slots
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("scheduler semaphore is never closed");
+
+        JobPermit {
+            _slot: slot,
+            _guards: guards,
+            _registration: self.registration,
+        }
+    }
+}
+
+// Held while the job runs.
+#[derive(Debug)]
+pub struct JobPermit {
+    _slot: OwnedSemaphorePermit,
+    _guards: Vec<OwnedMutexGuard<()>>,
+    _registration: Registration,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn same_key_waits_its_turn() {
+        let scheduler = Scheduler::new(4);
+
+        let first = scheduler.enqueue(vec!["host:c1r1s1".into()]);
+        let second = scheduler.enqueue(vec!["host:c1r1s1".into()]);
+        let other = scheduler.enqueue(vec!["host:c1r1s2".into()]);
+        assert_eq!(first.position(), 0);
+        assert_eq!(second.position(), 1);
+        assert_eq!(other.position(), 0);
+
+        let running = first.wait().await;
+        let _other = other.wait().await;
+        let second = tokio::spawn(second.wait());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!second.is_finished());
+
+        drop(running);
+        tokio::time::timeout(Duration::from_secs(1), second)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn global_limit_queues_extra_jobs() {
+        let scheduler = Scheduler::new(1);
+
+        let first = scheduler.enqueue(vec![]).wait().await;
+        let second = scheduler.enqueue(vec![]);
+        assert_eq!(second.position(), 1);
+        let third = scheduler.enqueue(vec![]);
+        assert_eq!(third.position(), 2);
+
+        drop(third);
+        drop(first);
+        let _second = second.wait().await;
+        assert_eq!(scheduler.enqueue(vec![]).position(), 1);
+    }
+}
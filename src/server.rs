@@ -1,336 +1,586 @@
 use crate::{
-    bot_cmd::{BotTask, GsctlCommand, GsctlError, SubCommand},
-    excutor::{RawCommand, SshExcutor},
+    bot_cmd::{help_text, BotTask, GsctlCommand, GsctlError, SlackMessageContext},
+    campus::CampusApi,
+    config::Config,
+    confirm::PendingConfirmations,
+    dedup::SeenEvents,
+    dispatch::{run_command, Backends},
+    error::{Action, BotError},
+    excutor::SshBackend,
     handler::*,
-    WAKEUP_WORD_FOR_USER,
+    homemaker::HomemakerClient,
+    i18n::tr,
+    jobs::{reconcile, Interrupted, JobStore},
+    link::AccountLinker,
+    location::LocationResolver,
+    reply,
+    scheduler::Scheduler,
+    socket_mode,
+    staff::SlackStaffNotifier,
+    user::{fetch_channel_logins, SyncReport, UserStore},
 };
-use ft_api::{config_env_var, FtClient, FtClientReqwestConnector};
+use serde::Deserialize;
 use slack_morphism::prelude::*;
 
-use tracing::{debug, *};
+use tracing::*;
 
 use axum::Extension;
+use std::convert::Infallible;
+use std::future::Future;
+use std::io;
+use std::str::FromStr;
 use std::sync::Arc;
-use tokio::{net::TcpListener, sync::mpsc, task};
+use std::time::Instant;
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::task::JoinSet;
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, watch},
+    task,
+};
+
+// `socket` opens a Socket Mode connection instead of waiting for Slack to
+// call the HTTP routes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    Http,
+    #[serde(rename = "socket")]
+    SocketMode,
+}
 
-const DEFAULT_PORT: u16 = 22;
+impl FromStr for Transport {
+    type Err = String;
 
-pub async fn run_slack_server() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let slack_client: Arc<SlackHyperClient> =
-        Arc::new(SlackClient::new(SlackClientHyperConnector::new()?));
+    fn from_str(transport: &str) -> Result<Self, Self::Err> {
+        match transport {
+            "http" => Ok(Transport::Http),
+            "socket" => Ok(Transport::SocketMode),
+            _ => Err("expected `http` or `socket`".to_string()),
+        }
+    }
+}
 
-    let addr = std::net::SocketAddr::from(([127, 0, 0, 1], 8080));
+pub async fn run_slack_server(
+    config: Config,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let config = Arc::new(config);
+    let slack_client: Arc<SlackHyperClient> = Arc::new(SlackClient::new(
+        SlackClientHyperConnector::new()?.with_rate_control(SlackApiRateControlConfig::new()),
+    ));
+
+    let addr = config.server.bind;
     info!("Loading server: {}", addr);
 
     let oauth_listener_config = SlackOAuthListenerConfig::new(
-        config_env_var("SLACK_CLIENT_ID")?.into(),
-        config_env_var("SLACK_CLIENT_SECRET")?.into(),
-        config_env_var("SLACK_BOT_SCOPE")?,
-        config_env_var("SLACK_REDIRECT_HOST")?,
+        config.slack.client_id.clone().into(),
+        config.slack.client_secret.clone().into(),
+        config.slack.bot_scope.clone(),
+        config.slack.redirect_host.clone(),
     );
 
     let listener_environment: Arc<SlackHyperListenerEnvironment> = Arc::new(
         SlackClientEventsListenerEnvironment::new(slack_client.clone())
             .with_error_handler(error_handler),
     );
-    let signing_secret: SlackSigningSecret = config_env_var("SLACK_SIGNING_SECRET")?.into();
 
     let listener: SlackEventsAxumListener<SlackHyperHttpsConnector> =
         SlackEventsAxumListener::new(listener_environment.clone());
 
+    let token = SlackApiToken::new(config.slack.bot_token.clone().into());
     let (sender, mut receiver) = mpsc::channel::<BotTask>(32);
 
-    let ft_client = Arc::new(FtClient::new(FtClientReqwestConnector::with_connector(
-        reqwest::Client::new(),
-    )));
-
-    // build our application route with OAuth nested router and Push/Command/Interaction events
-    let app = axum::routing::Router::new()
+    let locations = Arc::new(LocationResolver::new(Arc::new(CampusApi::new(
+        config.ft.token_lifetime,
+        config.ft.location_cache_ttl,
+    ))));
+    let users = Arc::new(UserStore::open(&config.storage.users)?);
+    let linker = Arc::new(AccountLinker::new(
+        config.ft.client_uid.clone(),
+        config.ft.client_secret.clone(),
+        config.ft.oauth_redirect_uri.clone(),
+    ));
+    let confirmations = Arc::new(PendingConfirmations::new(config.jobs.confirm_timeout));
+    let scheduler = Scheduler::new(config.jobs.max_concurrent);
+    let jobs = Arc::new(JobStore::open(&config.storage.jobs)?);
+    let backends = Backends {
+        executor: Arc::new(SshBackend {
+            ansible_cluster_port: config.ssh.ansible_cluster_port,
+            timeouts: config.timeouts,
+        }),
+        homes: Arc::new(HomemakerClient::new(
+            &config.homemaker.url,
+            config.homemaker.token.clone(),
+            config.timeouts,
+        )?),
+        staff: Arc::new(SlackStaffNotifier::new(
+            slack_client.clone(),
+            token.clone(),
+            config.slack.staff_channel.clone().map(SlackChannelId),
+        )),
+        staff_locale: config.slack.default_locale,
+    };
+    let pipeline = Pipeline {
+        client: slack_client.clone(),
+        config: config.clone(),
+        sender,
+        confirmations: confirmations.clone(),
+        seen: Arc::new(SeenEvents::default()),
+    };
+
+    // OAuth install and the 42 account link callback are served in both modes.
+    let mut app = axum::routing::Router::new()
         .nest(
             "/auth",
             listener.oauth_router("/auth", &oauth_listener_config, oauth_install_function),
         )
         .route(
-            "/push",
-            axum::routing::post(push_event)
-                .layer(Extension(sender))
-                .layer(
-                    listener
-                        .events_layer(&signing_secret)
-                        .with_event_extractor(SlackEventsExtractors::push_event()),
-                ),
+            "/link",
+            axum::routing::get(link_callback)
+                .layer::<_, Infallible>(Extension(config.clone()))
+                .layer::<_, Infallible>(Extension(linker.clone()))
+                .layer(Extension(users.clone())),
         );
 
-    task::spawn(async move {
-        axum::serve(TcpListener::bind(&addr).await.unwrap(), app)
-            .await
-            .unwrap();
+    let transport = config.server.transport;
+    info!("Receiving Slack events over {transport:?}");
+    let socket_mode = match transport {
+        Transport::Http => {
+            // The secret of the chosen transport is checked when the config is loaded.
+            let signing_secret: SlackSigningSecret = config
+                .slack
+                .signing_secret
+                .clone()
+                .unwrap_or_default()
+                .into();
+            app = app
+                .route(
+                    "/push",
+                    axum::routing::post(push_event)
+                        .layer::<_, Infallible>(Extension(pipeline.clone()))
+                        .layer(
+                            listener
+                                .events_layer(&signing_secret)
+                                .with_event_extractor(SlackEventsExtractors::push_event()),
+                        ),
+                )
+                .route(
+                    "/command",
+                    axum::routing::post(command_event)
+                        .layer::<_, Infallible>(Extension(pipeline.clone()))
+                        .layer(
+                            listener
+                                .events_layer(&signing_secret)
+                                .with_event_extractor(SlackEventsExtractors::command_event()),
+                        ),
+                )
+                .route(
+                    "/interaction",
+                    axum::routing::post(interaction_event)
+                        .layer::<_, Infallible>(Extension(pipeline))
+                        .layer(
+                            listener
+                                .events_layer(&signing_secret)
+                                .with_event_extractor(SlackEventsExtractors::interaction_event()),
+                        ),
+                );
+            None
+        }
+        Transport::SocketMode => {
+            let app_token =
+                SlackApiToken::new(config.slack.app_token.clone().unwrap_or_default().into());
+            Some(socket_mode::start(pipeline, &app_token).await?)
+        }
+    };
+
+    report_interrupted(
+        &slack_client,
+        &token,
+        &jobs,
+        &backends,
+        &config.slack.wakeup_word,
+    )
+    .await;
+
+    let shutdown = shutdown_signal()?;
+    tokio::pin!(shutdown);
+    let (stop_server, mut server_stopped) = watch::channel(false);
+
+    let tcp_listener = TcpListener::bind(&addr).await?;
+    let server = task::spawn(async move {
+        let serve = axum::serve(tcp_listener, app).with_graceful_shutdown(async move {
+            let _ = server_stopped.changed().await;
+        });
+        if let Err(err) = serve.await {
+            error!("Server stopped: {err}");
+        }
     });
 
-    while let Some(task) = receiver.recv().await {
-        let ft_client = ft_client.clone();
+    // Jobs still waiting for their turn give up once this is set.
+    let (cancel_waiting, waiting_cancelled) = watch::channel(false);
+    let mut running = JoinSet::new();
+    loop {
+        let task = tokio::select! {
+            _ = &mut shutdown => break,
+            Some(_) = running.join_next(), if !running.is_empty() => continue,
+            task = receiver.recv() => match task {
+                Some(task) => task,
+                None => break,
+            },
+        };
+
+        let locations = locations.clone();
+        let users = users.clone();
+        let linker = linker.clone();
         let slack_client = slack_client.clone();
-
-        task::spawn(async move {
-            let token = SlackApiToken::new(config_env_var("SLACK_TOKEN").unwrap().into());
+        let backends = backends.clone();
+        let token = token.clone();
+        let confirmations = confirmations.clone();
+        let scheduler = scheduler.clone();
+        let jobs = jobs.clone();
+        let config = config.clone();
+        let mut cancelled = waiting_cancelled.clone();
+
+        running.spawn(async move {
+            let slack = &config.slack;
             let session = slack_client.open_session(&token);
-
-            let result = match GsctlCommand::from(&task.message_context, ft_client).await {
-                Ok(command) => {
-                    let _ = session
-                        .reactions_add(&SlackApiReactionsAddRequest::new(
-                            task.message_context.channel.clone(),
-                            SlackReactionName::new("gsroot-loading".to_owned()),
-                            task.message_context.ts.clone(),
-                        ))
+            let (job, context, parsed) = match task {
+                BotTask::Request(request) => {
+                    let job = jobs.received(&request);
+                    let Some(context) =
+                        message_context(&session, request.clone(), slack.default_locale).await
+                    else {
+                        let context =
+                            SlackMessageContext::unknown_sender(request, slack.default_locale);
+                        reply::notice(
+                            &slack_client,
+                            &session,
+                            &context,
+                            tr(context.locale, "error.sender_unknown", &[]),
+                        )
                         .await;
-
-                    match command {
-                        GsctlCommand::Reboot(location) => {
-                            let port: u16 = config_env_var("ANSIBLE_CLUSTER_SSH_PORT")
-                                .ok()
-                                .and_then(|port| port.parse().ok())
-                                .unwrap_or(DEFAULT_PORT);
-                            let output = SshExcutor::new_ansible_cluster()
-                                .with_port(port)
-                                .with_remote_cmd(RawCommand::build_pc_reboot(&location))
-                                .execute()
-                                .await
-                                .unwrap();
-
-                            let stdout = String::from_utf8(output.stdout).unwrap_or_default();
-
-                            if output.status.success() {
-                                debug!("Reboot done: {stdout}");
-                                Ok(None)
-                            } else {
-                                debug!("Reboot failed with following error: {stdout}");
-                                Err(Some("Reboot failed.".to_string()))
-                            }
-                        }
-                        GsctlCommand::Home(subcommand) => {
-                            if let Some(subcmd) = subcommand {
-                                let host_url = config_env_var("STUDENT_STORAGE_API_URL").unwrap();
-                                let secret_token =
-                                    config_env_var("HOMEMAKER_SECRET_TOKEN").unwrap();
-                                let port: u16 = config_env_var("STUDENT_STORAGE_SSH_PORT")
-                                    .ok()
-                                    .and_then(|port| port.parse().ok())
-                                    .unwrap_or(DEFAULT_PORT);
-                                match subcmd {
-                                    SubCommand::Reset(login) => {
-                                        let delete_output = SshExcutor::new_student_storage()
-                                            .with_port(port)
-                                            .with_remote_cmd(RawCommand::build_home_delete(
-                                                &login.clone(),
-                                                &host_url,
-                                                &secret_token,
-                                            ))
-                                            .execute()
-                                            .await
-                                            .unwrap();
-
-                                        let create_output = SshExcutor::new_student_storage()
-                                            .with_port(port)
-                                            .with_remote_cmd(RawCommand::build_home_create(
-                                                &login.clone(),
-                                                &host_url,
-                                                &secret_token,
-                                            ))
-                                            .execute()
-                                            .await
-                                            .unwrap();
-
-                                        if delete_output.status.success() {
-                                            let create_stdout =
-                                                String::from_utf8(create_output.stdout)
-                                                    .unwrap_or_default();
-                                            let delete_stdout =
-                                                String::from_utf8(delete_output.stdout)
-                                                    .unwrap_or_default();
-                                            debug!("Home reset done.\ndelete:[{delete_stdout}]\ncreate:[{create_stdout}]");
-                                            Ok(None)
-                                        } else {
-                                            let create_stderr =
-                                                String::from_utf8(create_output.stderr)
-                                                    .unwrap_or_default();
-                                            let delete_stderr =
-                                                String::from_utf8(delete_output.stderr)
-                                                    .unwrap_or_default();
-                                            debug!(
-                                                "Home reset failed with following error: delete: {delete_stderr}, create: {create_stderr}"
-                                            );
-                                            Err(Some(
-                                                "Home reset failed. please contact staff"
-                                                    .to_string(),
-                                            ))
-                                        }
-                                    }
-                                    SubCommand::Close(login, location) => {
-                                        let output = SshExcutor::new_student_storage()
-                                            .with_remote_cmd(RawCommand::build_home_close(
-                                                &login,
-                                                &location,
-                                                &host_url,
-                                                &secret_token,
-                                            ))
-                                            .execute()
-                                            .await
-                                            .unwrap();
-
-                                        let stdout =
-                                            String::from_utf8(output.stdout).unwrap_or_default();
-
-                                        if output.status.success() {
-                                            debug!(
-                                                "Home close on {location}, login: {login} done: {stdout}"
-                                            );
-                                            Ok(None)
-                                        } else {
-                                            debug!(
-                                                "Home close on {location}, login: {login} failed with: {stdout}."
-                                            );
-                                            Err(Some("Home close failed.".to_string()))
-                                        }
-                                    }
-                                    SubCommand::Help => todo!(),
-                                }
-                            } else {
-                                Err(None)
-                            }
-                        }
-                        GsctlCommand::Goinfre(subcommand) => unimplemented!(),
-                        GsctlCommand::Update => {
-                            let res = session
-                                .conversations_members(
-                                    &SlackApiConversationsMembersRequest::new()
-                                        .with_limit(200)
-                                        .with_channel(task.message_context.channel.clone()),
-                                )
-                                .await;
-
-                            if res.is_ok() {
-                                let members = res.unwrap().members;
-                                let mut member_nickname_pair = vec![];
-
-                                for member in members {
-                                    let info = session
-                                        .users_info(&SlackApiUsersInfoRequest::new(member.clone()))
+                        jobs.finished(&job, Some("could not look up the sender".to_string()));
+                        return;
+                    };
+                    let parsed =
+                        match GsctlCommand::from(&context, &slack.wakeup_word, &locations, &users)
+                            .await
+                        {
+                            Ok(command) => {
+                                jobs.parsed(&job, context.locale, &command);
+                                match command.destructive_action() {
+                                    Some(action) => {
+                                        // The confirmed command comes back as a new job.
+                                        jobs.discard(&job);
+                                        let id = confirmations.insert(
+                                            context.clone(),
+                                            command,
+                                            Instant::now(),
+                                        );
+                                        reply::ask_confirmation(
+                                            &slack_client,
+                                            &session,
+                                            &context,
+                                            action,
+                                            &id,
+                                        )
                                         .await;
-
-                                    if let SlackApiUsersInfoResponse {
-                                        user:
-                                            SlackUser {
-                                                real_name: Some(name),
-                                                ..
-                                            },
-                                    } = info.unwrap()
-                                    {
-                                        member_nickname_pair.push((member, name))
+                                        return;
                                     }
+                                    None => Ok(command),
                                 }
-
-                                Ok(None)
-                            } else {
-                                Err(Some("get member list failed.".to_string()))
                             }
-                        }
-                    }
+                            Err(err) => Err(err),
+                        };
+                    (job, context, parsed)
+                }
+                BotTask::Confirmed {
+                    message_context,
+                    command,
+                } => {
+                    let job = jobs.received(&message_context.request());
+                    jobs.parsed(&job, message_context.locale, &command);
+                    (job, message_context, Ok(command))
                 }
-                Err(error) => match error {
-                    GsctlError::Help => Err(Some(format!(
-                        "```사용법: {WAKEUP_WORD_FOR_USER} [핵심 명령어] [하위 명령어]
-
-핵심 명령어:
-  reboot       시스템을 재부팅합니다.
-
-  home         'home' 디렉토리와 관련된 작업을 관리합니다.
-    하위 명령어:
-      reset    home을 기본 상태로 재설정합니다.
-      close    remote home과 pc의 연결을 끊습니다.
-
-일반 옵션:
-  -h, --help   이 도움말 메시지를 보여주고 종료합니다.
-
-예제:
-   {WAKEUP_WORD_FOR_USER} reboot
-   {WAKEUP_WORD_FOR_USER} home reset
-
-인식할 수 없는 명령어나 하위 명령어가 제공될 경우 이 도움말이 표시됩니다.```"
-                    ))),
-                    GsctlError::Error(msg) => {
-                        let command = task.message_context.text.clone();
-                        debug!("{} command error with: {msg}", command);
-                        Err(Some(format!(
-                            "Command cannot be executed for the following reasons: {msg}"
-                        )))
-                    }
-                    GsctlError::NotACommand => Err(None),
-                },
             };
+            let locale = context.locale;
 
-            match result {
-                Ok(res) => {
-                    let _ = session
-                        .reactions_remove(
-                            &SlackApiReactionsRemoveRequest::new(SlackReactionName::new(
-                                "gsroot-loading".to_owned(),
-                            ))
-                            .with_channel(task.message_context.channel.clone())
-                            .with_timestamp(task.message_context.ts.clone()),
-                        )
-                        .await;
-                    let _ = session
-                        .reactions_add(&SlackApiReactionsAddRequest::new(
-                            task.message_context.channel.clone(),
-                            SlackReactionName::new("white_check_mark".to_owned()),
-                            task.message_context.ts.clone(),
-                        ))
-                        .await;
+            let result = match parsed {
+                Ok(command) => {
+                    let ticket = scheduler.enqueue(command.job_keys());
+                    if ticket.position() > 0 {
+                        reply::queued(&slack_client, &session, &context, ticket.position()).await;
+                    }
+                    // A job that never started stays parsed and is reported
+                    // as not started once the shutdown is over.
+                    let _permit = tokio::select! {
+                        permit = ticket.wait() => permit,
+                        _ = cancelled.wait_for(|cancelled| *cancelled) => return,
+                    };
+                    if *cancelled.borrow() {
+                        return;
+                    }
+                    jobs.running(&job);
+                    reply::mark_running(&session, &context).await;
 
-                    if let Some(msg) = res {
-                        let _ = session
-                            .chat_post_message(
-                                &SlackApiChatPostMessageRequest::new(
-                                    task.message_context.channel,
-                                    SlackMessageContent::new().with_text(msg),
-                                )
-                                .with_thread_ts(task.message_context.ts),
-                            )
-                            .await;
+                    match command {
+                        GsctlCommand::Update => update_members(&session, &context, &users).await,
+                        GsctlCommand::Link => send_link(&session, &context, &linker).await,
+                        command => run_command(command, &backends).await,
                     }
+                    .map_err(Some)
                 }
-                Err(Some(msg)) => {
-                    let _ = session
-                        .reactions_remove(
-                            &SlackApiReactionsRemoveRequest::new(SlackReactionName::new(
-                                "gsroot-loading".to_owned(),
-                            ))
-                            .with_channel(task.message_context.channel.clone())
-                            .with_timestamp(task.message_context.ts.clone()),
-                        )
-                        .await;
-                    let _ = session
-                        .reactions_add(&SlackApiReactionsAddRequest::new(
-                            task.message_context.channel.clone(),
-                            SlackReactionName::new("x".to_owned()),
-                            task.message_context.ts.clone(),
-                        ))
-                        .await;
+                Err(GsctlError::Help(path)) => Ok(Some(format!(
+                    "```{}```",
+                    help_text(locale, &slack.wakeup_word_for_user, &path)
+                ))),
+                Err(GsctlError::Error(err)) => Err(Some(err)),
+                Err(GsctlError::NotACommand) => Err(None),
+            };
 
-                    let _ = session
-                        .chat_post_message(
-                            &SlackApiChatPostMessageRequest::new(
-                                task.message_context.channel,
-                                SlackMessageContent::new().with_text(msg),
-                            )
-                            .with_thread_ts(task.message_context.ts),
-                        )
-                        .await;
-                }
-                Err(None) => {}
+            match &result {
+                Ok(_) => jobs.finished(&job, None),
+                Err(Some(err)) => jobs.finished(&job, Some(err.to_string())),
+                Err(None) => jobs.discard(&job),
             }
+            reply::report(
+                &slack_client,
+                &session,
+                &context,
+                &slack.wakeup_word_for_user,
+                result,
+            )
+            .await;
         });
     }
+
+    // Stop taking events first, so nothing new starts while draining.
+    info!("Shutting down, {} jobs still running", running.len());
+    let _ = cancel_waiting.send(true);
+    let _ = stop_server.send(true);
+    if let Some(socket_mode) = &socket_mode {
+        socket_mode.shutdown().await;
+    }
+    drain_queued(&mut receiver, &jobs);
+
+    let grace = config.server.shutdown_grace;
+    let drained = tokio::time::timeout(grace, async {
+        while running.join_next().await.is_some() {}
+    })
+    .await;
+    if drained.is_err() {
+        warn!("{} jobs did not finish within {grace:?}", running.len());
+        running.shutdown().await;
+    }
+
+    report_interrupted(
+        &slack_client,
+        &token,
+        &jobs,
+        &backends,
+        &config.slack.wakeup_word,
+    )
+    .await;
+    jobs.flush().await;
+    let _ = server.await;
+    info!("Shut down");
     Ok(())
 }
+
+// Resolves on SIGTERM, which systemd sends on stop and restart, or SIGINT.
+fn shutdown_signal() -> io::Result<impl Future<Output = ()>> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+
+    Ok(async move {
+        tokio::select! {
+            _ = terminate.recv() => info!("SIGTERM received"),
+            _ = interrupt.recv() => info!("SIGINT received"),
+        }
+    })
+}
+
+async fn update_members(
+    session: &SlackClientSession<'_, SlackClientHyperHttpsConnector>,
+    context: &SlackMessageContext,
+    users: &UserStore,
+) -> Result<Option<String>, BotError> {
+    let members = fetch_channel_logins(session, &context.channel)
+        .await
+        .map_err(|err| BotError::Failed {
+            action: Action::Update,
+            detail: err.to_string(),
+        })?;
+    let SyncReport {
+        added,
+        changed,
+        removed,
+    } = users
+        .sync(&context.channel, members)
+        .map_err(BotError::Store)?;
+
+    Ok(Some(tr(
+        context.locale,
+        "update.done",
+        &[
+            ("added", &added.to_string()),
+            ("changed", &changed.to_string()),
+            ("removed", &removed.to_string()),
+        ],
+    )))
+}
+
+async fn send_link(
+    session: &SlackClientSession<'_, SlackClientHyperHttpsConnector>,
+    context: &SlackMessageContext,
+    linker: &AccountLinker,
+) -> Result<Option<String>, BotError> {
+    let url = linker.start(context.user.clone(), context.locale);
+    session
+        .chat_post_message(&SlackApiChatPostMessageRequest::new(
+            SlackChannelId(context.user.0.clone()),
+            SlackMessageContent::new().with_text(tr(
+                context.locale,
+                "link.dm",
+                &[("url", url.as_str())],
+            )),
+        ))
+        .await
+        .map_err(|err| BotError::Failed {
+            action: Action::Link,
+            detail: err.to_string(),
+        })?;
+
+    Ok(Some(tr(context.locale, "link.sent", &[])))
+}
+
+// Tells the requesters of jobs cut off by the last shutdown what happened,
+// and staff about the ones that may have left something half done.
+async fn report_interrupted(
+    slack_client: &SlackHyperClient,
+    token: &SlackApiToken,
+    jobs: &JobStore,
+    backends: &Backends,
+    wakeup_word: &str,
+) {
+    let session = slack_client.open_session(token);
+
+    for (context, notice) in interrupted_notices(jobs, backends, wakeup_word).await {
+        reply::interrupted(slack_client, &session, &context, notice).await;
+    }
+}
+
+async fn interrupted_notices(
+    jobs: &JobStore,
+    backends: &Backends,
+    wakeup_word: &str,
+) -> Vec<(SlackMessageContext, String)> {
+    let mut notices = vec![];
+
+    for (job, interrupted) in reconcile(jobs, backends.homes.as_ref(), wakeup_word).await {
+        warn!("Job {} was interrupted in state {:?}", job.id, job.state);
+        let locale = job.locale.unwrap_or(backends.staff_locale);
+        let command = job
+            .action
+            .map(|action| action.name().to_string())
+            .or_else(|| job.request.command_text(wakeup_word))
+            .unwrap_or_else(|| job.request.text.clone());
+        let context = SlackMessageContext::unknown_sender(job.request, locale);
+
+        let key = match interrupted {
+            Interrupted::NotStarted => "job.interrupted_not_started",
+            Interrupted::Running { home } => {
+                let state = match home {
+                    Some(Ok(status)) => status.state,
+                    Some(Err(err)) => err,
+                    None => "-".to_string(),
+                };
+                backends
+                    .staff
+                    .notify(tr(
+                        backends.staff_locale,
+                        "staff.job_interrupted",
+                        &[
+                            ("command", &command),
+                            ("user", &context.user.0),
+                            ("state", &state),
+                        ],
+                    ))
+                    .await;
+                "job.interrupted_running"
+            }
+        };
+        notices.push((context, tr(locale, key, &[("command", &command)])));
+    }
+
+    notices
+}
+
+// Records the tasks still queued at shutdown, so they are reported as not
+// started like any other cut-off job.
+fn drain_queued(receiver: &mut mpsc::Receiver<BotTask>, jobs: &JobStore) {
+    receiver.close();
+    while let Ok(task) = receiver.try_recv() {
+        match task {
+            BotTask::Request(request) => {
+                jobs.received(&request);
+            }
+            BotTask::Confirmed {
+                message_context,
+                command,
+            } => {
+                let job = jobs.received(&message_context.request());
+                jobs.parsed(&job, message_context.locale, &command);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bot_cmd::{MessageSource, SlackRequest};
+    use crate::excutor::ScriptedBackend;
+    use crate::homemaker::ScriptedHomes;
+    use crate::i18n::Locale;
+    use crate::staff::RecordingNotifier;
+    use crate::test_util::store_path;
+
+    #[tokio::test]
+    async fn queued_requests_are_reported_as_not_started() {
+        let path = store_path("drain");
+        let jobs = JobStore::open(&path).unwrap();
+        let backends = Backends {
+            executor: Arc::new(ScriptedBackend::new()),
+            homes: Arc::new(ScriptedHomes::new()),
+            staff: Arc::new(RecordingNotifier::new()),
+            staff_locale: Locale::En,
+        };
+        let (sender, mut receiver) = mpsc::channel(4);
+        for text in ["<@BOT> reboot", "thanks"] {
+            let request = SlackRequest {
+                user: SlackUserId("U1".to_string()),
+                channel: SlackChannelId("C1".to_string()),
+                source: MessageSource::Mention {
+                    ts: SlackTs("1".to_string()),
+                    thread_ts: None,
+                },
+                text: text.to_string(),
+            };
+            sender.send(BotTask::Request(request)).await.unwrap();
+        }
+
+        drain_queued(&mut receiver, &jobs);
+        let notices = interrupted_notices(&jobs, &backends, "<@BOT>").await;
+
+        assert_eq!(notices.len(), 1);
+        assert_eq!(notices[0].0.user, SlackUserId("U1".to_string()));
+        assert_eq!(
+            notices[0].1,
+            "The bot restarted before `reboot` could run. Please send it again."
+        );
+        assert!(jobs.unfinished().is_empty());
+        drop(jobs);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,70 @@
+use slack_morphism::prelude::*;
+use std::sync::Arc;
+
+use crate::handler::{error_handler, Pipeline};
+
+pub type SocketModeListener = SlackClientSocketModeListener<SlackClientHyperHttpsConnector>;
+
+async fn pipeline(states: &SlackClientEventsUserState) -> Result<Pipeline, crate::Error> {
+    states
+        .read()
+        .await
+        .get_user_state::<Pipeline>()
+        .cloned()
+        .ok_or_else(|| "socket mode listener started without a pipeline".into())
+}
+
+async fn on_push(
+    event: SlackPushEventCallback,
+    _client: Arc<SlackHyperClient>,
+    states: SlackClientEventsUserState,
+) -> UserCallbackResult<()> {
+    pipeline(&states).await?.on_push(event).await;
+    Ok(())
+}
+
+async fn on_command(
+    event: SlackCommandEvent,
+    _client: Arc<SlackHyperClient>,
+    states: SlackClientEventsUserState,
+) -> UserCallbackResult<SlackCommandEventResponse> {
+    pipeline(&states).await?.on_command(event).await;
+    // An empty ack, the reply goes to the response url like over HTTP.
+    Ok(SlackCommandEventResponse::new(SlackMessageContent::new()))
+}
+
+async fn on_interaction(
+    event: SlackInteractionEvent,
+    _client: Arc<SlackHyperClient>,
+    states: SlackClientEventsUserState,
+) -> UserCallbackResult<SlackInteractionResponse> {
+    pipeline(&states).await?.on_interaction(event).await;
+    Ok(SlackInteractionResponse::Empty)
+}
+
+// Receives events over a websocket opened by the bot, so Slack never has to
+// reach us. The listener stops when it is dropped.
+pub async fn start(
+    pipeline: Pipeline,
+    app_token: &SlackApiToken,
+) -> Result<SocketModeListener, crate::Error> {
+    let callbacks = SlackSocketModeListenerCallbacks::new()
+        .with_push_events(on_push)
+        .with_command_events(on_command)
+        .with_interaction_events(on_interaction);
+
+    let environment = Arc::new(
+        SlackClientEventsListenerEnvironment::new(pipeline.client.clone())
+            .with_error_handler(error_handler)
+            .with_user_state(pipeline),
+    );
+
+    let listener = SlackClientSocketModeListener::new(
+        &SlackClientSocketModeConfig::new(),
+        environment,
+        callbacks,
+    );
+    listener.listen_for(app_token).await?;
+    listener.start().await;
+    Ok(listener)
+}
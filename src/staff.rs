@@ -0,0 +1,81 @@
+use slack_morphism::prelude::*;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+#[cfg(test)]
+use std::sync::Mutex;
+use tracing::{error, warn};
+
+pub type NotifyFuture<'a> = Pin<Box<dyn Future<Output = ()> + Send + 'a>>;
+
+pub trait StaffNotifier: Send + Sync {
+    fn notify(&self, message: String) -> NotifyFuture<'_>;
+}
+
+pub struct SlackStaffNotifier {
+    client: Arc<SlackHyperClient>,
+    token: SlackApiToken,
+    channel: Option<SlackChannelId>,
+}
+
+impl SlackStaffNotifier {
+    pub fn new(
+        client: Arc<SlackHyperClient>,
+        token: SlackApiToken,
+        channel: Option<SlackChannelId>,
+    ) -> Self {
+        SlackStaffNotifier {
+            client,
+            token,
+            channel,
+        }
+    }
+}
+
+impl StaffNotifier for SlackStaffNotifier {
+    fn notify(&self, message: String) -> NotifyFuture<'_> {
+        Box::pin(async move {
+            let Some(channel) = &self.channel else {
+                warn!("No staff channel is configured, staff alert: {message}");
+                return;
+            };
+
+            let res = self
+                .client
+                .open_session(&self.token)
+                .chat_post_message(&SlackApiChatPostMessageRequest::new(
+                    channel.clone(),
+                    SlackMessageContent::new().with_text(message.clone()),
+                ))
+                .await;
+            if let Err(err) = res {
+                error!("Could not alert staff ({err}): {message}");
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct RecordingNotifier {
+    messages: Mutex<Vec<String>>,
+}
+
+#[cfg(test)]
+impl RecordingNotifier {
+    pub fn new() -> Self {
+        RecordingNotifier::default()
+    }
+
+    pub fn messages(&self) -> Vec<String> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+impl StaffNotifier for RecordingNotifier {
+    fn notify(&self, message: String) -> NotifyFuture<'_> {
+        self.messages.lock().unwrap().push(message);
+        Box::pin(async {})
+    }
+}
@@ -0,0 +1,10 @@
+use std::fs;
+use std::path::PathBuf;
+
+// A file in the temp dir, unique to this test process and removed first.
+pub fn store_path(name: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("gs-slack-bot-{}-{name}.json", std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
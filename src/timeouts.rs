@@ -0,0 +1,58 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+use crate::config::secs;
+use crate::{excutor::CommandKind, homemaker::HomeAction};
+
+// In the config file as `[timeouts]`, each key in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Timeouts {
+    #[serde(rename = "reboot_secs", deserialize_with = "secs")]
+    pub reboot: Duration,
+    #[serde(rename = "goinfre_reset_secs", deserialize_with = "secs")]
+    pub goinfre_reset: Duration,
+    #[serde(rename = "command_secs", deserialize_with = "secs")]
+    pub adhoc: Duration,
+    #[serde(rename = "home_create_secs", deserialize_with = "secs")]
+    pub home_create: Duration,
+    #[serde(rename = "home_delete_secs", deserialize_with = "secs")]
+    pub home_delete: Duration,
+    #[serde(rename = "home_close_secs", deserialize_with = "secs")]
+    pub home_close: Duration,
+    #[serde(rename = "home_status_secs", deserialize_with = "secs")]
+    pub home_status: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            reboot: Duration::from_secs(180),
+            goinfre_reset: Duration::from_secs(600),
+            adhoc: Duration::from_secs(60),
+            home_create: Duration::from_secs(60),
+            home_delete: Duration::from_secs(120),
+            home_close: Duration::from_secs(30),
+            home_status: Duration::from_secs(10),
+        }
+    }
+}
+
+impl Timeouts {
+    pub fn command(&self, kind: CommandKind) -> Duration {
+        match kind {
+            CommandKind::Reboot => self.reboot,
+            CommandKind::GoinfreReset => self.goinfre_reset,
+            CommandKind::Adhoc => self.adhoc,
+        }
+    }
+
+    pub fn home(&self, action: HomeAction) -> Duration {
+        match action {
+            HomeAction::Create => self.home_create,
+            HomeAction::Delete => self.home_delete,
+            HomeAction::Close => self.home_close,
+            HomeAction::Status => self.home_status,
+        }
+    }
+}
@@ -1,14 +1,363 @@
 use ft_api::FtLoginId;
 use serde::{Deserialize, Serialize};
-use slack_morphism::SlackUserId;
+use slack_morphism::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct FtSlackIdLogin {
-    slack_id: SlackUserId,
-    login: FtLoginId,
+const SLACK_PAGE_LIMIT: u16 = 200;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkSource {
+    #[default]
+    Manual,
+    Synced,
+    Verified,
+}
+
+impl LinkSource {
+    // Synced entries come from Slack profile names, which anyone can edit.
+    pub fn is_trusted(self) -> bool {
+        self != LinkSource::Synced
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FtSlackIdLogin {
+    pub slack_id: SlackUserId,
+    pub login: FtLoginId,
+    #[serde(default)]
+    pub source: LinkSource,
+    // The channel a synced entry came from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub synced_from: Option<SlackChannelId>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SyncReport {
+    pub added: usize,
+    pub changed: usize,
+    pub removed: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct SlackUserList {
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlackUserList {
     vec: Vec<FtSlackIdLogin>,
 }
+
+impl SlackUserList {
+    pub fn get(&self, slack_id: &SlackUserId) -> Option<&FtSlackIdLogin> {
+        self.vec.iter().find(|user| &user.slack_id == slack_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &FtSlackIdLogin> {
+        self.vec.iter()
+    }
+
+    // A login belongs to exactly one Slack account, so binding it again moves it.
+    pub fn bind(&mut self, slack_id: SlackUserId, login: FtLoginId, source: LinkSource) {
+        self.vec
+            .retain(|user| user.slack_id != slack_id && user.login != login);
+        self.vec.push(FtSlackIdLogin {
+            slack_id,
+            login,
+            source,
+            synced_from: None,
+        });
+    }
+
+    // Replaces the entries synced from `channel` with its current `members`.
+    // Entries synced from other channels or bound any other way are kept, and
+    // a synced member never takes over a login bound any other way.
+    pub fn sync(
+        &mut self,
+        channel: &SlackChannelId,
+        members: Vec<(SlackUserId, FtLoginId)>,
+    ) -> SyncReport {
+        let before: HashMap<_, _> = self
+            .vec
+            .iter()
+            .map(|user| (user.slack_id.clone(), user.login.clone()))
+            .collect();
+
+        self.vec.retain(|user| {
+            user.source != LinkSource::Synced || user.synced_from.as_ref() != Some(channel)
+        });
+        for (slack_id, login) in members {
+            let taken = self.vec.iter().any(|user| {
+                user.source != LinkSource::Synced
+                    && (user.slack_id == slack_id || user.login == login)
+            });
+            if !taken {
+                self.bind(slack_id, login, LinkSource::Synced);
+                if let Some(user) = self.vec.last_mut() {
+                    user.synced_from = Some(channel.clone());
+                }
+            }
+        }
+
+        let after: HashMap<_, _> = self
+            .vec
+            .iter()
+            .map(|user| (&user.slack_id, &user.login))
+            .collect();
+        SyncReport {
+            added: after.keys().filter(|id| !before.contains_key(**id)).count(),
+            changed: after
+                .iter()
+                .filter(|(id, login)| before.get(**id).is_some_and(|old| old != **login))
+                .count(),
+            removed: before.keys().filter(|id| !after.contains_key(id)).count(),
+        }
+    }
+
+    pub fn remove(&mut self, slack_id: &SlackUserId) -> Option<FtSlackIdLogin> {
+        let index = self
+            .vec
+            .iter()
+            .position(|user| &user.slack_id == slack_id)?;
+        Some(self.vec.remove(index))
+    }
+}
+
+#[derive(Debug)]
+pub struct UserStore {
+    path: PathBuf,
+    users: RwLock<SlackUserList>,
+}
+
+impl UserStore {
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let users = match fs::read(&path) {
+            Ok(raw) => serde_json::from_slice(&raw)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => SlackUserList::default(),
+            Err(err) => return Err(err),
+        };
+
+        Ok(UserStore {
+            path,
+            users: RwLock::new(users),
+        })
+    }
+
+    pub fn login(&self, slack_id: &SlackUserId) -> Option<FtLoginId> {
+        self.user(slack_id).map(|user| user.login)
+    }
+
+    pub fn user(&self, slack_id: &SlackUserId) -> Option<FtSlackIdLogin> {
+        self.users.read().unwrap().get(slack_id).cloned()
+    }
+
+    pub fn snapshot(&self) -> SlackUserList {
+        self.users.read().unwrap().clone()
+    }
+
+    pub fn bind(
+        &self,
+        slack_id: SlackUserId,
+        login: FtLoginId,
+        source: LinkSource,
+    ) -> io::Result<()> {
+        self.update(|users| users.bind(slack_id, login, source))
+    }
+
+    pub fn sync(
+        &self,
+        channel: &SlackChannelId,
+        members: Vec<(SlackUserId, FtLoginId)>,
+    ) -> io::Result<SyncReport> {
+        self.update(|users| users.sync(channel, members))
+    }
+
+    pub fn remove(&self, slack_id: &SlackUserId) -> io::Result<Option<FtSlackIdLogin>> {
+        self.update(|users| users.remove(slack_id))
+    }
+
+    // Changes are written to disk before they become visible, so a failed
+    // write leaves both the file and the in-memory list untouched.
+    fn update<T>(&self, change: impl FnOnce(&mut SlackUserList) -> T) -> io::Result<T> {
+        let mut users = self.users.write().unwrap();
+        let mut next = users.clone();
+        let result = change(&mut next);
+
+        persist(&self.path, &next)?;
+        *users = next;
+
+        Ok(result)
+    }
+}
+
+// Channel members paired with the 42 login taken from their Slack real name.
+// Both lists are paged, and `users.list` fetches profiles in batches instead
+// of one `users.info` call per member.
+pub async fn fetch_channel_logins(
+    session: &SlackClientSession<'_, SlackClientHyperHttpsConnector>,
+    channel: &SlackChannelId,
+) -> ClientResult<Vec<(SlackUserId, FtLoginId)>> {
+    let mut members = HashSet::new();
+    let mut cursor = None;
+    loop {
+        let page = session
+            .conversations_members(
+                &SlackApiConversationsMembersRequest::new()
+                    .with_channel(channel.clone())
+                    .with_limit(SLACK_PAGE_LIMIT)
+                    .opt_cursor(cursor),
+            )
+            .await?;
+        members.extend(page.members);
+
+        cursor = page.response_metadata.and_then(|meta| meta.next_cursor);
+        if cursor.is_none() {
+            break;
+        }
+    }
+
+    let mut logins = vec![];
+    let mut cursor = None;
+    loop {
+        let page = session
+            .users_list(
+                &SlackApiUsersListRequest::new()
+                    .with_limit(SLACK_PAGE_LIMIT)
+                    .opt_cursor(cursor),
+            )
+            .await?;
+        logins.extend(page.members.into_iter().filter_map(|user| {
+            let is_bot = user.flags.is_bot.unwrap_or(false);
+            let deleted = user.deleted.unwrap_or(false);
+            match user.real_name {
+                Some(name) if members.contains(&user.id) && !is_bot && !deleted => {
+                    Some((user.id, FtLoginId(name)))
+                }
+                _ => None,
+            }
+        }));
+
+        cursor = page.response_metadata.and_then(|meta| meta.next_cursor);
+        if cursor.is_none() {
+            break;
+        }
+    }
+
+    Ok(logins)
+}
+
+pub(crate) fn persist<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let raw = serde_json::to_vec_pretty(value).map_err(io::Error::other)?;
+    let tmp = path.with_extension("json.tmp");
+
+    fs::write(&tmp, raw)?;
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::store_path;
+
+    fn slack_id(id: &str) -> SlackUserId {
+        SlackUserId(id.to_string())
+    }
+
+    fn login(login: &str) -> FtLoginId {
+        FtLoginId(login.to_string())
+    }
+
+    #[test]
+    fn bindings_survive_reopen() {
+        let path = store_path("reopen");
+        let store = UserStore::open(&path).unwrap();
+        store
+            .bind(slack_id("U1"), login("student"), LinkSource::Manual)
+            .unwrap();
+
+        let reopened = UserStore::open(&path).unwrap();
+
+        assert_eq!(reopened.login(&slack_id("U1")), Some(login("student")));
+        assert_eq!(reopened.login(&slack_id("U2")), None);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn login_is_bound_to_one_account() {
+        let mut users = SlackUserList::default();
+        users.bind(slack_id("U1"), login("student"), LinkSource::Manual);
+        users.bind(slack_id("U2"), login("student"), LinkSource::Manual);
+        users.bind(slack_id("U2"), login("other"), LinkSource::Manual);
+
+        assert_eq!(users.get(&slack_id("U1")), None);
+        assert_eq!(users.iter().count(), 1);
+        assert_eq!(users.get(&slack_id("U2")).unwrap().login, login("other"));
+    }
+
+    #[test]
+    fn sync_reports_changes_and_keeps_manual_entries() {
+        let mut users = SlackUserList::default();
+        let channel = SlackChannelId("C1".to_string());
+        users.bind(slack_id("U1"), login("staff"), LinkSource::Manual);
+        users.sync(
+            &channel,
+            vec![
+                (slack_id("U2"), login("old")),
+                (slack_id("U3"), login("gone")),
+            ],
+        );
+
+        let report = users.sync(
+            &channel,
+            vec![
+                (slack_id("U1"), login("renamed")),
+                (slack_id("U2"), login("new")),
+                (slack_id("U4"), login("staff")),
+                (slack_id("U5"), login("student")),
+            ],
+        );
+
+        assert_eq!(
+            report,
+            SyncReport {
+                added: 1,
+                changed: 1,
+                removed: 1,
+            }
+        );
+        assert_eq!(users.get(&slack_id("U1")).unwrap().login, login("staff"));
+        assert_eq!(users.get(&slack_id("U4")), None);
+    }
+
+    #[test]
+    fn sync_only_replaces_members_of_its_channel() {
+        let mut users = SlackUserList::default();
+        let small = SlackChannelId("C-small".to_string());
+        let cohort = SlackChannelId("C-cohort".to_string());
+        users.sync(
+            &cohort,
+            vec![
+                (slack_id("U1"), login("one")),
+                (slack_id("U2"), login("two")),
+            ],
+        );
+
+        let report = users.sync(&small, vec![(slack_id("U3"), login("three"))]);
+        assert_eq!(
+            report,
+            SyncReport {
+                added: 1,
+                changed: 0,
+                removed: 0,
+            }
+        );
+        assert_eq!(users.iter().count(), 3);
+
+        let report = users.sync(&cohort, vec![(slack_id("U1"), login("one"))]);
+        assert_eq!(report.removed, 1);
+        assert_eq!(users.get(&slack_id("U2")), None);
+        assert_eq!(users.get(&slack_id("U3")).unwrap().login, login("three"));
+    }
+}